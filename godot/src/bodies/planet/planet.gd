@@ -39,4 +39,5 @@ func generate() -> void:
 		var face := child as PlanetMeshFace
 		if face:
 			face.regenerate_mesh(planet_data)
+	physical_radius = planet_data.radius
 	generate_atmosphere()
@@ -18,6 +18,7 @@ var colors := [
 @export var radius := 5.0:
 	set(r):
 		radius = r
+		physical_radius = r
 		if (star_mesh == null):
 			star_mesh = $StarMesh
 		star_mesh.set_radius(r)
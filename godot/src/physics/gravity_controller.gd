@@ -16,6 +16,10 @@ func _on_orbit_visibility_change(visible):
 	set(val):
 		self.clear_trajectories()
 
+@export var AssignOrbitalVelocities: bool:
+	set(val):
+		self.assign_orbital_velocities()
+
 var show_trajectories_ingame := false:
 	set(enabled):
 		show_trajectories_ingame = enabled
@@ -28,4 +32,5 @@ var _dt := 0.
 func _process(delta: float) -> void:
 	if show_trajectories_ingame:
 		self.poll_trajectory_results()
-		self.queue_simulate_trajectories()
+		if not self.is_predicting_trajectories():
+			self.queue_simulate_trajectories()
@@ -4,7 +4,7 @@
 
 use criterion::{AxisScale, BatchSize, BenchmarkId, Criterion, PlotConfiguration, black_box};
 use criterion_macro::criterion;
-use glam::Vec3A;
+use glam::{Quat, Vec3A};
 use godot::prelude::*;
 use rand::{Rng, SeedableRng, rngs::StdRng};
 use rayon::{
@@ -18,7 +18,11 @@ use rust_gdext::{
         old_versions::{insert_based::InsertBasedOctree, partition_based::PartitionBasedOctree},
     },
     physics::gravity::{
-        NBodyGravityCalculator, controller::SimulatedBody, direct_summation::DirectSummation,
+        NBodyGravityCalculator,
+        broadphase::{Broadphase, BruteForce, SpatialHashGrid, SweepAndPrune},
+        controller::SimulatedBody,
+        direct_summation::DirectSummation,
+        motion::BodyMotion,
     },
 };
 
@@ -43,6 +47,11 @@ pub fn create_bench_bodies(n: u32) -> Vec<SimulatedBody> {
                 radius * phi.cos(),
             ),
             vel: Vec3A::ZERO,
+            orientation: Quat::IDENTITY,
+            angular_velocity: Vec3A::ZERO,
+            radius: None,
+            zonal_harmonics: [0.0; 3],
+            motion: BodyMotion::Dynamic,
         });
     }
 
@@ -125,6 +134,46 @@ fn compute_accelerations(c: &mut Criterion) {
     group.finish();
 }
 
+#[criterion(custom_criterion())]
+fn collision_broadphase(c: &mut Criterion) {
+    let mut group = c.benchmark_group("collision_broadphase");
+    group.plot_config(log_plotter());
+
+    let mut rng = StdRng::seed_from_u64(BENCH_SEED);
+
+    for size in [10, 20, 50, 100, 500, 1000, 5000, 20000, 100_000] {
+        // Constant density, so the number of collisions grows linearly with the size
+        let extent = 50.0 * (size as f32).cbrt();
+        let (positions, radii): (Vec<_>, Vec<_>) = (0..size)
+            .map(|_| {
+                let pos = Vec3A::new(
+                    rng.random_range(-extent..extent),
+                    rng.random_range(-extent..extent),
+                    rng.random_range(-extent..extent),
+                );
+                (pos, rng.random_range(0.5..10.0))
+            })
+            .unzip();
+
+        let broadphases: [(&str, &dyn Broadphase); 3] = [
+            ("brute_force", &BruteForce),
+            ("sweep_and_prune", &SweepAndPrune),
+            ("spatial_hash_grid", &SpatialHashGrid::default()),
+        ];
+
+        for (name, broadphase) in broadphases {
+            if name == "brute_force" && size > 5000 {
+                continue;
+            }
+
+            group.bench_function(BenchmarkId::new(name, size), |b| {
+                b.iter(|| black_box(broadphase.find_collisions(&positions, &radii)));
+            });
+        }
+    }
+    group.finish();
+}
+
 #[criterion]
 fn octree_build(c: &mut Criterion) {
     let mut group = c.benchmark_group("octree_build");
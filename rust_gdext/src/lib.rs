@@ -1,5 +1,4 @@
 #![feature(iter_collect_into)]
-#![feature(anonymous_lifetime_in_impl_trait)]
 #![feature(array_windows)]
 #![feature(assert_matches)]
 
@@ -20,3 +19,11 @@ pub fn to_glam_vec3(v: Vector3) -> glam::Vec3A {
 pub fn from_glam_vec3(v: glam::Vec3A) -> Vector3 {
     Vector3::new(v.x, v.y, v.z)
 }
+
+pub fn to_glam_quat(q: Quaternion) -> glam::Quat {
+    glam::Quat::from_xyzw(q.x, q.y, q.z, q.w)
+}
+
+pub fn from_glam_quat(q: glam::Quat) -> Quaternion {
+    Quaternion::new(q.x, q.y, q.z, q.w)
+}
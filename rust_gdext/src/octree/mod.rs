@@ -2,7 +2,9 @@ pub mod morton_based;
 pub mod old_versions;
 pub mod visualize;
 
-use crate::physics::gravity::{HasMass, HasPosition, PosMass, controller::SimulatedBody};
+use crate::physics::gravity::{
+    HasMass, HasOblateness, HasPosition, PosMass, controller::SimulatedBody,
+};
 use core::array;
 use either::Either;
 use glam::Vec3A;
@@ -33,6 +35,8 @@ impl HasPosition for GravityData {
     }
 }
 
+impl HasOblateness for GravityData {}
+
 impl HasMass for GravityData {
     #[inline(always)]
     fn get_mass(&self) -> f32 {
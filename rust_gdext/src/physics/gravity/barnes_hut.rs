@@ -1,8 +1,9 @@
-use super::{GRAVITATIONAL_SOFTENING_SQUARED, NBodyGravityCalculator, PosMass, merge_radius}; // Added import
-use crate::octree::{BoundingBox, GravityData, morton_based::MortonBasedOctree};
+use super::{GRAVITATIONAL_SOFTENING_SQUARED, HasOblateness, NBodyGravityCalculator, PosMass};
+use crate::octree::{GravityData, morton_based::MortonBasedOctree};
 use glam::Vec3A;
+use godot::builtin::math::FloatExt;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
-use std::{assert_matches::debug_assert_matches, marker::Sync};
+use std::{debug_assert_matches, marker::Sync};
 
 // --- Constants for Barnes-Hut ---
 
@@ -12,7 +13,7 @@ const THETA_SQ: f32 = THETA * THETA;
 
 impl<'a, T> NBodyGravityCalculator<T> for MortonBasedOctree<'a, T>
 where
-    T: PosMass + Sync,
+    T: PosMass + HasOblateness + Sync,
 {
     /// Calculates the accelerations of particles using the Barnes-Hut algorithm.
     ///
@@ -42,43 +43,44 @@ where
         }
     }
 
-    fn detect_collisions(&self, merge_scaler: f32) -> Vec<(usize, usize)> {
-        // Check if the octree is empty or has no root node
-        let root_idx = match self.root_index {
-            Some(idx) if !self.data_ref.is_empty() => idx,
-            _ => return Vec::new(),
+    fn calc_accs_at<const PARALLEL: bool>(&self, g: f32, points: &[Vec3A]) -> Vec<Vec3A> {
+        if self.data_ref.is_empty() {
+            return vec![Vec3A::ZERO; points.len()];
+        }
+
+        if PARALLEL {
+            points
+                .into_par_iter()
+                .map(|&point| self.calculate_accel_at_point(g, point))
+                .collect()
+        } else {
+            points
+                .iter()
+                .map(|&point| self.calculate_accel_at_point(g, point))
+                .collect()
+        }
+    }
+
+    fn calc_potentials_at<const PARALLEL: bool>(&self, g: f32, points: &[Vec3A]) -> Vec<f32> {
+        let Some(root_idx) = self.root_index.filter(|_| !self.data_ref.is_empty()) else {
+            return vec![0.0; points.len()];
         };
 
-        (0..self.data_ref.len())
-            .into_par_iter()
-            .flat_map_iter(|i| {
-                let body_a = &self.data_ref[i];
-                let body_a_pos = body_a.get_pos();
-                let body_a_mass = body_a.get_mass();
-
-                let body_a_aabb = BoundingBox {
-                    center: body_a_pos,
-                    half_width: merge_radius(merge_scaler, body_a_mass, body_a_mass),
-                };
-
-                let mut potential_collisions_a = Vec::new();
-                self.find_collisions_for_body_recursive(
-                    root_idx,
-                    i,
-                    &body_a_pos,
-                    body_a_mass,
-                    &body_a_aabb,
-                    merge_scaler,
-                    &mut potential_collisions_a,
-                );
-
-                potential_collisions_a.into_iter().map(move |j| (i, j))
-            })
-            .collect()
+        if PARALLEL {
+            points
+                .into_par_iter()
+                .map(|&point| self.calculate_potential_recursive(g, root_idx, point))
+                .collect()
+        } else {
+            points
+                .iter()
+                .map(|&point| self.calculate_potential_recursive(g, root_idx, point))
+                .collect()
+        }
     }
 }
 
-impl<'a, T: PosMass + Sync> MortonBasedOctree<'a, T> {
+impl<'a, T: PosMass + HasOblateness + Sync> MortonBasedOctree<'a, T> {
     /// Calculates the total acceleration on a single target particle using Barnes-Hut.
     #[inline]
     fn calculate_accel_on_particle(&self, g: f32, target_particle_index: usize) -> Vec3A {
@@ -100,6 +102,64 @@ impl<'a, T: PosMass + Sync> MortonBasedOctree<'a, T> {
         )
     }
 
+    /// Calculates the total acceleration at a point that is not part of the octree.
+    #[inline]
+    fn calculate_accel_at_point(&self, g: f32, point: Vec3A) -> Vec3A {
+        debug_assert_matches!(
+            self.root_index,
+            Some(idx) if idx < self.nodes.len(),
+            "This function should only be called on a valid octree with a root node."
+        );
+
+        // No particle has this index, so no interaction is skipped as self-interaction
+        self.calculate_accel_recursive(g, self.root_index.unwrap(), usize::MAX, point)
+    }
+
+    /// Potential at a point that is not part of the octree, using the same opening
+    /// criterion as the accelerations.
+    fn calculate_potential_recursive(
+        &self,
+        g: f32,
+        current_node_index: usize,
+        point: Vec3A,
+    ) -> f32 {
+        let node = &self.nodes[current_node_index];
+        let dist_sq = node.data.center_of_mass.distance_squared(point);
+        let node_width = node.bounds.half_width * 2.0;
+
+        if node_width * node_width < THETA_SQ * dist_sq {
+            return -g * node.data.mass / (dist_sq + GRAVITATIONAL_SOFTENING_SQUARED).sqrt();
+        }
+
+        if let Some(children) = node.children {
+            return children
+                .iter()
+                .flatten()
+                .map(|child_index| self.calculate_potential_recursive(g, child_index.get(), point))
+                .sum();
+        }
+
+        node.body_range
+            .clone()
+            .map(|i| &self.data_ref[self.sorted_indices[i].item])
+            .map(|particle| {
+                // Like direct summation, only a particle located exactly at the point is skipped
+                let delta_pos = particle.get_pos() - point;
+                let dist_sq = delta_pos.length_squared();
+                if dist_sq.is_zero_approx() {
+                    return 0.0;
+                }
+
+                let mu = g * particle.get_mass();
+                let potential = -mu / (dist_sq + GRAVITATIONAL_SOFTENING_SQUARED).sqrt();
+                match particle.get_zonal_harmonics() {
+                    Some(harmonics) => potential + harmonics.potential(mu, -delta_pos),
+                    None => potential,
+                }
+            })
+            .sum()
+    }
+
     /// Recursive helper function for Barnes-Hut traversal.
     fn calculate_accel_recursive(
         &self,
@@ -220,7 +280,15 @@ impl<'a, T: PosMass + Sync> MortonBasedOctree<'a, T> {
                     return Vec3A::ZERO; // Avoid division by zero
                 }
 
-                (g * particle_mass / direct_dist_cubed) * direct_delta_pos
+                let acc = (g * particle_mass / direct_dist_cubed) * direct_delta_pos;
+
+                // Near-field interactions include the zonal harmonics of non-spherical bodies
+                match particle.get_zonal_harmonics() {
+                    Some(harmonics) => {
+                        acc + harmonics.acceleration(g * particle_mass, -direct_delta_pos)
+                    }
+                    None => acc,
+                }
             })
             .sum()
     }
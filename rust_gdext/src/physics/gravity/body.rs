@@ -1,10 +1,24 @@
-use crate::from_glam_vec3;
+use crate::{from_glam_quat, from_glam_vec3, physics::gravity::radius_from_density, to_glam_vec3};
 
-use super::controller::{
-    __gdext_GravityController_Funcs as GravityController_Funcs, GravityController, SimulatedBody,
+use super::{
+    controller::{
+        __gdext_GravityController_Funcs as GravityController_Funcs, GravityController,
+        SimulatedBody,
+    },
+    maneuver::{Maneuver, ManeuverFrame},
+    motion::{BodyMotion, KinematicPath, MotionMode},
+    orbit::OrbitalElements,
+};
+use godot::{
+    builtin::math::ApproxEq,
+    classes::{Curve3D, notify::Node3DNotification},
+    prelude::*,
 };
-use godot::{builtin::math::ApproxEq, classes::notify::Node3DNotification, prelude::*};
 use proc::editor;
+use std::{cell::OnceCell, sync::Arc};
+
+/// Hue difference between the path colors before and after a maneuver, in turns
+const MANEUVER_HUE_SHIFT: f32 = 0.15;
 
 /// A gravity-affected node in a physics simulation.
 ///
@@ -30,13 +44,177 @@ pub struct GravityBody {
     #[var(get, set = set_velocity)]
     pub velocity: Vector3,
 
+    /// Whether the body is moved by gravity, pinned in place, or moved kinematically.
+    ///
+    /// Pinned and kinematic bodies still attract all other bodies.
+    #[export]
+    #[var(get, set = set_motion_mode)]
+    pub motion_mode: MotionMode,
+
+    /// Path followed by a kinematic body, in the same space as its position.
+    ///
+    /// Without a path, a kinematic body is expected to be moved by an animation or script,
+    /// and trajectory predictions hold it in place. The path is baked when first simulated,
+    /// so changes to the points of the curve take effect once it is assigned again.
+    #[export]
+    #[var(get, set = set_kinematic_path)]
+    pub kinematic_path: Option<Gd<Curve3D>>,
+
+    /// Time in seconds for a kinematic body to traverse its whole path
+    #[export]
+    #[var(get, set = set_kinematic_path_duration)]
+    #[init(val = 10.0)]
+    pub kinematic_path_duration: f32,
+
+    /// Whether a kinematic body restarts its path after reaching the end
+    #[export]
+    #[var(get, set = set_kinematic_path_loop)]
+    #[init(val = true)]
+    pub kinematic_path_loop: bool,
+
+    /// Body orbited by this body when on rails.
+    ///
+    /// The orbit follows from the current position and velocity relative to the parent,
+    /// so switching between on rails and dynamic at runtime is seamless. Without a
+    /// parent, the body is integrated like a dynamic one.
+    #[export]
+    #[var(get, set = set_rails_parent)]
+    pub rails_parent: Option<Gd<GravityBody>>,
+
+    /// Body that the orbital elements below are relative to.
+    ///
+    /// Setting any of the elements places this body on the described orbit around it,
+    /// using the controller's `grav_const`. They are not updated as the body moves,
+    /// call `update_orbital_elements` to read them back from the current state.
+    #[export]
+    #[var(get, set = set_orbit_parent)]
+    pub orbit_parent: Option<Gd<GravityBody>>,
+
+    /// Semi-major axis of the orbit, or the periapsis distance for parabolic orbits
+    #[export]
+    #[var(get, set = set_semi_major_axis)]
+    pub semi_major_axis: f32,
+
+    /// Eccentricity of the orbit, 0 for circular orbits
+    #[export]
+    #[var(get, set = set_eccentricity)]
+    pub eccentricity: f32,
+
+    /// Tilt of the orbital plane against the XZ plane, in degrees
+    #[export]
+    #[var(get, set = set_inclination)]
+    pub inclination: f32,
+
+    /// Angle from +X to the ascending node, in degrees
+    #[export]
+    #[var(get, set = set_longitude_of_ascending_node)]
+    pub longitude_of_ascending_node: f32,
+
+    /// Angle from the ascending node to the periapsis, in degrees
+    #[export]
+    #[var(get, set = set_argument_of_periapsis)]
+    pub argument_of_periapsis: f32,
+
+    /// Angle from the periapsis to the body, in degrees
+    #[export]
+    #[var(get, set = set_true_anomaly)]
+    pub true_anomaly: f32,
+
+    /// Angular velocity of the body in radians per second, around the axis it points along.
+    ///
+    /// The simulation rotates the node accordingly. Merges conserve angular momentum, so
+    /// the orbital angular momentum of absorbed bodies speeds up the rotation.
+    #[export]
+    pub angular_velocity: Vector3,
+
+    /// The physical radius of the body, used for collisions.
+    ///
+    /// When zero, the radius is derived from `density` instead.
+    #[export]
+    #[var(get, set = set_physical_radius)]
+    pub physical_radius: f32,
+
+    /// The density of the body, used to derive its radius from its mass when
+    /// `physical_radius` is zero.
+    ///
+    /// When both are zero, collisions use a mass-based heuristic scaled by the
+    /// controller's `merge_scaler`.
+    #[export]
+    #[var(get, set = set_density)]
+    pub density: f32,
+
+    /// Zonal harmonic `J2` of the gravity field, describing the equatorial bulge of a
+    /// rotating body. Orbits around the body precess when it's non-zero.
+    ///
+    /// Applied around the rotation axis, relative to the physical radius, so it
+    /// requires a physical radius or density.
+    #[export]
+    #[var(get, set = set_j2)]
+    pub j2: f32,
+
+    /// Zonal harmonic `J3` of the gravity field, a north-south asymmetry
+    #[export]
+    #[var(get, set = set_j3)]
+    pub j3: f32,
+
+    /// Zonal harmonic `J4` of the gravity field
+    #[export]
+    #[var(get, set = set_j4)]
+    pub j4: f32,
+
+    /// A constant force acting on the body, like engine thrust
+    #[export]
+    #[var(get, set = set_thrust)]
+    pub thrust: Vector3,
+
+    /// Bodies closer than this to this body are slowed down by its atmosphere.
+    /// Zero disables drag.
+    #[export]
+    #[var(get, set = set_drag_range)]
+    pub drag_range: f32,
+
+    /// Atmospheric drag proportional to the velocity relative to this body
+    #[export]
+    #[var(get, set = set_linear_drag)]
+    pub linear_drag: f32,
+
+    /// Atmospheric drag proportional to the squared velocity relative to this body
+    #[export]
+    #[var(get, set = set_quadratic_drag)]
+    pub quadratic_drag: f32,
+
+    /// Radiated power pushing away bodies with a radiation pressure coefficient
+    #[export]
+    #[var(get, set = set_luminosity)]
+    pub luminosity: f32,
+
+    /// How strongly this body is pushed away by luminous bodies
+    #[export]
+    #[var(get, set = set_radiation_pressure_coefficient)]
+    pub radiation_pressure_coefficient: f32,
+
+    /// Called every physics step with the body's position and velocity,
+    /// returning an extra force (`Vector3`) acting on the body.
+    ///
+    /// Trajectory predictions call it once and hold the returned force constant, so they
+    /// are inaccurate for forces that depend on the position, velocity or time.
+    #[var]
+    #[init(val = Callable::invalid())]
+    pub force_callable: Callable,
+
     /// The color used to render this body's trajectory
     #[export]
     pub trajectory_color: Color,
 
+    /// Planned velocity changes, ordered by time. Executed maneuvers are removed.
+    pub maneuvers: Vec<Maneuver>,
+
     /// The ancestor controller, if any
     controller: Option<Gd<GravityController>>,
 
+    /// Baked `kinematic_path`, cleared whenever one of the path properties changes
+    kinematic_path_cache: OnceCell<Arc<KinematicPath>>,
+
     /// Track the last position to detect changes
     last_position: Vector3,
 
@@ -83,11 +261,320 @@ impl GravityBody {
         self.emit_update_trajectories();
     }
 
+    #[func]
+    pub fn set_motion_mode(&mut self, value: MotionMode) {
+        self.motion_mode = value;
+        self.emit_update_trajectories();
+    }
+
+    #[func]
+    pub fn set_kinematic_path(&mut self, value: Option<Gd<Curve3D>>) {
+        self.kinematic_path = value;
+        self.kinematic_path_cache.take();
+        self.emit_update_trajectories();
+    }
+
+    #[func]
+    pub fn set_kinematic_path_duration(&mut self, value: f32) {
+        self.kinematic_path_duration = value;
+        self.kinematic_path_cache.take();
+        self.emit_update_trajectories();
+    }
+
+    #[func]
+    pub fn set_kinematic_path_loop(&mut self, value: bool) {
+        self.kinematic_path_loop = value;
+        self.kinematic_path_cache.take();
+        self.emit_update_trajectories();
+    }
+
+    #[func]
+    pub fn set_rails_parent(&mut self, value: Option<Gd<GravityBody>>) {
+        self.rails_parent = value;
+        self.emit_update_trajectories();
+    }
+
+    #[func]
+    pub fn set_orbit_parent(&mut self, value: Option<Gd<GravityBody>>) {
+        self.orbit_parent = value;
+        self.update_orbital_elements();
+    }
+
+    #[func]
+    pub fn set_semi_major_axis(&mut self, value: f32) {
+        self.semi_major_axis = value;
+        self.apply_orbital_elements();
+    }
+
+    #[func]
+    pub fn set_eccentricity(&mut self, value: f32) {
+        self.eccentricity = value;
+        self.apply_orbital_elements();
+    }
+
+    #[func]
+    pub fn set_inclination(&mut self, value: f32) {
+        self.inclination = value;
+        self.apply_orbital_elements();
+    }
+
+    #[func]
+    pub fn set_longitude_of_ascending_node(&mut self, value: f32) {
+        self.longitude_of_ascending_node = value;
+        self.apply_orbital_elements();
+    }
+
+    #[func]
+    pub fn set_argument_of_periapsis(&mut self, value: f32) {
+        self.argument_of_periapsis = value;
+        self.apply_orbital_elements();
+    }
+
+    #[func]
+    pub fn set_true_anomaly(&mut self, value: f32) {
+        self.true_anomaly = value;
+        self.apply_orbital_elements();
+    }
+
+    /// Reads the orbital elements back from the current position and velocity
+    /// relative to `orbit_parent`.
+    ///
+    /// Leaves the elements unchanged if there is no parent or the orbit is degenerate.
+    #[func]
+    pub fn update_orbital_elements(&mut self) {
+        let Some(elements) = self.current_orbital_elements() else {
+            return;
+        };
+
+        self.semi_major_axis = elements.semi_major_axis;
+        self.eccentricity = elements.eccentricity;
+        self.inclination = elements.inclination.to_degrees();
+        self.longitude_of_ascending_node = elements.longitude_of_ascending_node.to_degrees();
+        self.argument_of_periapsis = elements.argument_of_periapsis.to_degrees();
+        self.true_anomaly = elements.true_anomaly.to_degrees();
+    }
+
+    /// Returns the classical orbital elements around `orbit_parent` as a dictionary.
+    ///
+    /// Contains `semi_major_axis`, `eccentricity`, `inclination`,
+    /// `longitude_of_ascending_node`, `argument_of_periapsis` and `true_anomaly`, with
+    /// angles in degrees. Empty if there is no parent or the orbit is degenerate.
+    #[func]
+    pub fn get_orbital_elements(&self) -> Dictionary {
+        let Some(elements) = self.current_orbital_elements() else {
+            return Dictionary::new();
+        };
+
+        dict! {
+            "semi_major_axis": elements.semi_major_axis,
+            "eccentricity": elements.eccentricity,
+            "inclination": elements.inclination.to_degrees(),
+            "longitude_of_ascending_node": elements.longitude_of_ascending_node.to_degrees(),
+            "argument_of_periapsis": elements.argument_of_periapsis.to_degrees(),
+            "true_anomaly": elements.true_anomaly.to_degrees(),
+        }
+    }
+
+    /// Places the body on the orbit around `orbit_parent` described by `elements`.
+    ///
+    /// Takes the same keys as returned by `get_orbital_elements`. Missing keys keep
+    /// their current value.
+    #[func]
+    pub fn set_orbital_elements(&mut self, elements: Dictionary) {
+        let get = |key: &str, current: f32| {
+            elements
+                .get(key)
+                .and_then(|v| v.try_to::<f32>().ok())
+                .unwrap_or(current)
+        };
+
+        self.semi_major_axis = get("semi_major_axis", self.semi_major_axis);
+        self.eccentricity = get("eccentricity", self.eccentricity);
+        self.inclination = get("inclination", self.inclination);
+        self.longitude_of_ascending_node = get(
+            "longitude_of_ascending_node",
+            self.longitude_of_ascending_node,
+        );
+        self.argument_of_periapsis = get("argument_of_periapsis", self.argument_of_periapsis);
+        self.true_anomaly = get("true_anomaly", self.true_anomaly);
+
+        self.apply_orbital_elements();
+    }
+
     #[func]
     pub fn set_mass(&mut self, value: f32) {
         self.mass = value;
         self.emit_update_trajectories();
     }
+
+    #[func]
+    pub fn set_j2(&mut self, value: f32) {
+        self.j2 = value;
+        self.emit_update_trajectories();
+    }
+
+    #[func]
+    pub fn set_j3(&mut self, value: f32) {
+        self.j3 = value;
+        self.emit_update_trajectories();
+    }
+
+    #[func]
+    pub fn set_j4(&mut self, value: f32) {
+        self.j4 = value;
+        self.emit_update_trajectories();
+    }
+
+    #[func]
+    pub fn set_thrust(&mut self, value: Vector3) {
+        self.thrust = value;
+        self.emit_update_trajectories();
+    }
+
+    #[func]
+    pub fn set_drag_range(&mut self, value: f32) {
+        self.drag_range = value;
+        self.emit_update_trajectories();
+    }
+
+    #[func]
+    pub fn set_linear_drag(&mut self, value: f32) {
+        self.linear_drag = value;
+        self.emit_update_trajectories();
+    }
+
+    #[func]
+    pub fn set_quadratic_drag(&mut self, value: f32) {
+        self.quadratic_drag = value;
+        self.emit_update_trajectories();
+    }
+
+    #[func]
+    pub fn set_luminosity(&mut self, value: f32) {
+        self.luminosity = value;
+        self.emit_update_trajectories();
+    }
+
+    #[func]
+    pub fn set_radiation_pressure_coefficient(&mut self, value: f32) {
+        self.radiation_pressure_coefficient = value;
+        self.emit_update_trajectories();
+    }
+
+    #[func]
+    pub fn set_physical_radius(&mut self, value: f32) {
+        self.physical_radius = value;
+        self.emit_update_trajectories();
+    }
+
+    #[func]
+    pub fn set_density(&mut self, value: f32) {
+        self.density = value;
+        self.emit_update_trajectories();
+    }
+
+    /// Plans a velocity change at the simulation time `time`.
+    ///
+    /// With the orbital frame, X, Y and Z of `delta_v` are the prograde, normal and radial
+    /// components relative to the body's primary. The path after the maneuver is drawn in
+    /// a shifted hue of `trajectory_color`, see `set_maneuver_color`.
+    ///
+    /// Returns the index of the new maneuver, maneuvers are ordered by time.
+    #[func]
+    pub fn add_maneuver(&mut self, time: f64, delta_v: Vector3, frame: ManeuverFrame) -> i64 {
+        let hue_shift = MANEUVER_HUE_SHIFT * (self.maneuvers.len() + 1) as f32;
+        let color = match self.trajectory_color.try_to_hsv() {
+            Ok(mut hsv) => {
+                hsv.h += hue_shift;
+                hsv.normalized_wrapped_h().to_rgb()
+            }
+            Err(_) => self.trajectory_color,
+        };
+
+        let index = self.insert_maneuver(Maneuver {
+            time,
+            delta_v: to_glam_vec3(delta_v),
+            frame,
+            color,
+        });
+        self.emit_update_trajectories();
+
+        index as i64
+    }
+
+    /// Moves a maneuver to another time.
+    ///
+    /// Returns the new index of the maneuver, or -1 if the index is invalid.
+    #[func]
+    pub fn move_maneuver(&mut self, index: i64, time: f64) -> i64 {
+        let Some(i) = self.maneuver_index(index) else {
+            return -1;
+        };
+
+        let maneuver = self.maneuvers.remove(i);
+        let index = self.insert_maneuver(Maneuver { time, ..maneuver });
+        self.emit_update_trajectories();
+
+        index as i64
+    }
+
+    /// Changes the velocity change of a maneuver.
+    #[func]
+    pub fn set_maneuver_delta_v(&mut self, index: i64, delta_v: Vector3, frame: ManeuverFrame) {
+        let Some(i) = self.maneuver_index(index) else {
+            return;
+        };
+
+        self.maneuvers[i].delta_v = to_glam_vec3(delta_v);
+        self.maneuvers[i].frame = frame;
+        self.emit_update_trajectories();
+    }
+
+    /// Changes the color of the predicted path after a maneuver.
+    #[func]
+    pub fn set_maneuver_color(&mut self, index: i64, color: Color) {
+        let Some(i) = self.maneuver_index(index) else {
+            return;
+        };
+
+        self.maneuvers[i].color = color;
+        self.emit_update_trajectories();
+    }
+
+    /// Removes a maneuver. Later maneuvers move down one index.
+    #[func]
+    pub fn remove_maneuver(&mut self, index: i64) {
+        let Some(i) = self.maneuver_index(index) else {
+            return;
+        };
+
+        self.maneuvers.remove(i);
+        self.emit_update_trajectories();
+    }
+
+    /// Removes all maneuvers.
+    #[func]
+    pub fn clear_maneuvers(&mut self) {
+        self.maneuvers.clear();
+        self.emit_update_trajectories();
+    }
+
+    /// Returns the planned maneuvers ordered by time, as dictionaries with their `time`,
+    /// `delta_v`, `frame` and `color`.
+    #[func]
+    pub fn get_maneuvers(&self) -> Array<Dictionary> {
+        self.maneuvers
+            .iter()
+            .map(|m| {
+                dict! {
+                    "time": m.time,
+                    "delta_v": from_glam_vec3(m.delta_v),
+                    "frame": m.frame,
+                    "color": m.color,
+                }
+            })
+            .collect()
+    }
 }
 
 impl GravityBody {
@@ -108,10 +595,136 @@ impl GravityBody {
         self.last_position = current_pos;
     }
 
+    /// Inserts a maneuver after all maneuvers at the same time or earlier, returning its index.
+    fn insert_maneuver(&mut self, maneuver: Maneuver) -> usize {
+        let index = self.maneuvers.partition_point(|m| m.time <= maneuver.time);
+        self.maneuvers.insert(index, maneuver);
+        index
+    }
+
+    /// Checks a maneuver index from Godot.
+    fn maneuver_index(&self, index: i64) -> Option<usize> {
+        let i = usize::try_from(index)
+            .ok()
+            .filter(|&i| i < self.maneuvers.len());
+        if i.is_none() {
+            godot_error!("Maneuver index out of bounds: {}", index);
+        }
+        i
+    }
+
+    /// Returns the physical radius, derived from the density if no radius is set.
+    pub fn resolved_radius(&self) -> Option<f32> {
+        if self.physical_radius > 0.0 {
+            Some(self.physical_radius)
+        } else if self.density > 0.0 {
+            Some(radius_from_density(self.mass, self.density))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the motion of the body for the simulation, baking its kinematic path once.
+    pub fn motion(&self) -> BodyMotion {
+        match self.motion_mode {
+            MotionMode::Dynamic => BodyMotion::Dynamic,
+            MotionMode::Pinned => BodyMotion::Pinned,
+            MotionMode::Kinematic => {
+                BodyMotion::Kinematic(self.kinematic_path.as_ref().map(|curve| {
+                    self.kinematic_path_cache
+                        .get_or_init(|| {
+                            Arc::new(KinematicPath {
+                                points: curve
+                                    .get_baked_points()
+                                    .as_slice()
+                                    .iter()
+                                    .map(|&p| to_glam_vec3(p))
+                                    .collect(),
+                                duration: self.kinematic_path_duration,
+                                looping: self.kinematic_path_loop,
+                            })
+                        })
+                        .clone()
+                }))
+            }
+            MotionMode::OnRails => match &self.rails_parent {
+                Some(parent) => BodyMotion::OnRails(parent.instance_id()),
+                None => BodyMotion::Dynamic,
+            },
+        }
+    }
+
     pub fn update_from_sim(&mut self, sim: &SimulatedBody) {
+        // Only a merge changes the radius, which then no longer follows from the density
+        if sim.radius != self.resolved_radius() {
+            self.physical_radius = sim.radius.unwrap_or_default();
+        }
+
         self.mass = sim.mass;
         self.velocity = from_glam_vec3(sim.vel);
+        self.angular_velocity = from_glam_vec3(sim.angular_velocity);
         self.base_mut().set_position(from_glam_vec3(sim.pos));
+        self.base_mut()
+            .set_quaternion(from_glam_quat(sim.orientation));
+    }
+
+    /// Returns the orbit parent and the gravitational parameter of the orbit around it.
+    ///
+    /// Returns `None` without a controller, without a parent, or if the body is its own parent.
+    fn orbit_parent_and_mu(&self) -> Option<(Gd<GravityBody>, f32)> {
+        let parent = self.orbit_parent.clone()?;
+        if parent == self.to_gd() {
+            return None;
+        }
+
+        let grav_const = self.controller.as_ref()?.bind().grav_const;
+        let mu = grav_const * (parent.bind().mass + self.mass);
+
+        Some((parent, mu))
+    }
+
+    /// Computes the orbital elements from the current state relative to `orbit_parent`.
+    fn current_orbital_elements(&self) -> Option<OrbitalElements> {
+        let (parent, mu) = self.orbit_parent_and_mu()?;
+
+        let rel_pos = self.base().get_position() - parent.get_position();
+        let rel_vel = self.velocity - parent.bind().velocity;
+
+        OrbitalElements::from_state(mu, to_glam_vec3(rel_pos), to_glam_vec3(rel_vel))
+    }
+
+    /// Sets the position and velocity from the orbital elements around `orbit_parent`.
+    fn apply_orbital_elements(&mut self) {
+        // Properties are set one by one while loading, the stored state is already correct
+        if !self.base().is_node_ready() {
+            return;
+        }
+
+        let Some((parent, mu)) = self.orbit_parent_and_mu() else {
+            return;
+        };
+
+        let elements = OrbitalElements {
+            semi_major_axis: self.semi_major_axis,
+            eccentricity: self.eccentricity,
+            inclination: self.inclination.to_radians(),
+            longitude_of_ascending_node: self.longitude_of_ascending_node.to_radians(),
+            argument_of_periapsis: self.argument_of_periapsis.to_radians(),
+            true_anomaly: self.true_anomaly.to_radians(),
+        };
+
+        let Some((rel_pos, rel_vel)) = elements.to_state(mu) else {
+            godot_warn!(
+                "Orbital elements of {} describe no valid orbit",
+                self.base().get_name()
+            );
+            return;
+        };
+
+        let parent_vel = parent.bind().velocity;
+        self.base_mut()
+            .set_position(parent.get_position() + from_glam_vec3(rel_pos));
+        self.set_velocity(parent_vel + from_glam_vec3(rel_vel));
     }
 
     /// Traverses up the node tree to find the parent `GravityController`.
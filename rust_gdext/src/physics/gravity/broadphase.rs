@@ -0,0 +1,316 @@
+//! Collision broadphases.
+//!
+//! A broadphase finds every pair of bodies whose bounding spheres overlap, without
+//! testing all `N²` pairs. All implementations share the [`Broadphase`] trait and return
+//! exactly the same pairs as [`BruteForce`], which is used as the reference in tests.
+//!
+//! - [`BruteForce`]: tests every pair, fastest for a handful of bodies
+//! - [`SweepAndPrune`]: sorts bodies along the axis with the largest spread and only tests
+//!   bodies whose intervals overlap on that axis
+//! - [`SpatialHashGrid`]: buckets bodies into uniform cells and only tests neighboring cells
+
+use glam::{IVec3, Vec3A};
+
+/// Finds the pairs of bodies whose bounding spheres overlap.
+pub trait Broadphase {
+    /// Returns every pair `(i, j)` with `i < j` where the distance between `positions[i]`
+    /// and `positions[j]` is less than `radii[i] + radii[j]`.
+    ///
+    /// The order of the returned pairs is unspecified.
+    fn find_collisions(&self, positions: &[Vec3A], radii: &[f32]) -> Vec<(usize, usize)>;
+}
+
+#[inline(always)]
+fn spheres_overlap(positions: &[Vec3A], radii: &[f32], i: usize, j: usize) -> bool {
+    let touch_dist = radii[i] + radii[j];
+    positions[i].distance_squared(positions[j]) < touch_dist * touch_dist
+}
+
+/// Tests every pair of bodies.
+pub struct BruteForce;
+
+impl Broadphase for BruteForce {
+    fn find_collisions(&self, positions: &[Vec3A], radii: &[f32]) -> Vec<(usize, usize)> {
+        let len = positions.len();
+
+        (0..len)
+            .flat_map(|i| ((i + 1)..len).map(move |j| (i, j)))
+            .filter(|&(i, j)| spheres_overlap(positions, radii, i, j))
+            .collect()
+    }
+}
+
+/// Sweep-and-prune along the axis where the bodies are most spread out.
+///
+/// Each body is projected to the interval `[p - r, p + r]` on that axis. The intervals are
+/// sorted by their start and swept, so only bodies with overlapping intervals are tested.
+pub struct SweepAndPrune;
+
+impl SweepAndPrune {
+    /// Returns the axis (0, 1 or 2) with the largest variance of positions.
+    fn dominant_axis(positions: &[Vec3A]) -> usize {
+        let n = positions.len().max(1) as f32;
+        let mean = positions.iter().copied().sum::<Vec3A>() / n;
+        let variance = positions
+            .iter()
+            .map(|&p| (p - mean) * (p - mean))
+            .sum::<Vec3A>();
+
+        if variance.x >= variance.y && variance.x >= variance.z {
+            0
+        } else if variance.y >= variance.z {
+            1
+        } else {
+            2
+        }
+    }
+}
+
+impl Broadphase for SweepAndPrune {
+    fn find_collisions(&self, positions: &[Vec3A], radii: &[f32]) -> Vec<(usize, usize)> {
+        let axis = Self::dominant_axis(positions);
+
+        // (interval start, interval end, body index), sorted by start
+        let mut intervals = positions
+            .iter()
+            .zip(radii)
+            .enumerate()
+            .map(|(i, (p, r))| (p[axis] - r, p[axis] + r, i))
+            .collect::<Vec<_>>();
+        intervals.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut collisions = Vec::new();
+        for (k, &(_, end_a, a)) in intervals.iter().enumerate() {
+            // Every later interval that starts before this one ends overlaps it on the axis
+            for &(_, _, b) in intervals[k + 1..]
+                .iter()
+                .take_while(|&&(start_b, _, _)| start_b < end_a)
+            {
+                if spheres_overlap(positions, radii, a, b) {
+                    collisions.push((a.min(b), a.max(b)));
+                }
+            }
+        }
+
+        collisions
+    }
+}
+
+/// Uniform grid where only occupied cells are stored.
+///
+/// Bodies are inserted into the cell containing their center. With cells at least as wide
+/// as the largest possible collision distance, colliding bodies are always in the same or
+/// in adjacent cells. Cells are stored as a list of bodies sorted by cell key, so a cell is
+/// found with a binary search instead of hashing.
+#[derive(Default)]
+pub struct SpatialHashGrid {
+    /// Width of a grid cell.
+    ///
+    /// When `None`, the cell width is twice the largest radius, the smallest width that
+    /// still only requires checking adjacent cells, but at least the extent of the bodies
+    /// divided by `∛N` so tiny radii can't spread the bodies over billions of cells.
+    /// Smaller values are raised to twice the largest radius.
+    pub cell_size: Option<f32>,
+}
+
+impl SpatialHashGrid {
+    /// Bits per axis in a cell key
+    const KEY_BITS: u32 = 21;
+    const KEY_MASK: i64 = (1 << Self::KEY_BITS) - 1;
+
+    /// Offsets `(dy, dz)` to the neighboring rows of cells that come "after" a cell.
+    ///
+    /// Together with the next cell in the same row, these cover every adjacent cell with a
+    /// larger key, so every pair of adjacent cells is visited exactly once.
+    const FORWARD_ROWS: [(i32, i32); 4] = [(1, 0), (-1, 1), (0, 1), (1, 1)];
+
+    /// Packs cell coordinates into a single key, with x in the lowest bits so that the
+    /// cells of a row along x have consecutive keys.
+    ///
+    /// Coordinates wrap around after `2^21` cells per axis, which can only place distant
+    /// bodies in the same cell. That costs extra sphere tests but never misses a collision.
+    #[inline]
+    fn cell_key(cell: IVec3) -> u64 {
+        let [x, y, z] = cell.to_array().map(|c| (c as i64 & Self::KEY_MASK) as u64);
+        (z << (2 * Self::KEY_BITS)) | (y << Self::KEY_BITS) | x
+    }
+
+    /// Returns the cell width used for the given bodies.
+    fn cell_size_for(&self, positions: &[Vec3A], radii: &[f32]) -> f32 {
+        let min_cell_size = 2.0 * radii.iter().copied().fold(0.0, f32::max);
+
+        let cell_size = self.cell_size.unwrap_or_else(|| {
+            let (min, max) = positions
+                .iter()
+                .fold((Vec3A::INFINITY, Vec3A::NEG_INFINITY), |(min, max), &p| {
+                    (min.min(p), max.max(p))
+                });
+            let extent = (max - min).max_element().max(0.0);
+            extent / (positions.len().max(1) as f32).cbrt()
+        });
+
+        cell_size.max(min_cell_size).max(f32::EPSILON)
+    }
+}
+
+impl Broadphase for SpatialHashGrid {
+    fn find_collisions(&self, positions: &[Vec3A], radii: &[f32]) -> Vec<(usize, usize)> {
+        let inv_cell_size = 1.0 / self.cell_size_for(positions, radii);
+        let cell_of = |p: Vec3A| (p * inv_cell_size).floor().as_ivec3();
+
+        // (cell key, body index), sorted so the bodies of each cell are contiguous
+        let mut entries = positions
+            .iter()
+            .enumerate()
+            .map(|(i, &p)| (Self::cell_key(cell_of(p)), i))
+            .collect::<Vec<_>>();
+        entries.sort_unstable();
+
+        // Bodies in the cells with keys in `keys`
+        let bodies_in = |keys: std::ops::RangeInclusive<u64>| {
+            let start = entries.partition_point(|&(k, _)| k < *keys.start());
+            let end = start + entries[start..].partition_point(|&(k, _)| k <= *keys.end());
+            &entries[start..end]
+        };
+
+        let mut collisions = Vec::new();
+        let mut test_against = |cell_bodies: &[(u64, usize)], others: &[(u64, usize)]| {
+            for &(_, b) in others {
+                for &(_, a) in cell_bodies {
+                    if spheres_overlap(positions, radii, a, b) {
+                        collisions.push((a.min(b), a.max(b)));
+                    }
+                }
+            }
+        };
+
+        for cell_bodies in entries.chunk_by(|a, b| a.0 == b.0) {
+            // Pairs within the cell
+            for (k, &(_, a)) in cell_bodies.iter().enumerate() {
+                test_against(&[(0, a)], &cell_bodies[k + 1..]);
+            }
+
+            let cell = cell_of(positions[cell_bodies[0].1]);
+            let key = cell_bodies[0].0;
+
+            // The next cell in the same row. Cells saturate at the limits of `i32`, so the
+            // offsets wrap like the keys do.
+            let next_key = Self::cell_key(cell.wrapping_add(IVec3::X));
+            if next_key != key {
+                test_against(cell_bodies, bodies_in(next_key..=next_key));
+            }
+
+            // The three cells of each neighboring row
+            for (dy, dz) in Self::FORWARD_ROWS {
+                let row = cell.wrapping_add(IVec3::new(0, dy, dz));
+                let first = Self::cell_key(row.wrapping_sub(IVec3::X));
+                let last = Self::cell_key(row.wrapping_add(IVec3::X));
+
+                if first < last {
+                    test_against(cell_bodies, bodies_in(first..=last));
+                } else {
+                    // The row wraps around in key space, look up the cells one by one
+                    for dx in -1..=1 {
+                        let neighbor_key = Self::cell_key(row.wrapping_add(IVec3::new(dx, 0, 0)));
+                        if neighbor_key != key {
+                            test_against(cell_bodies, bodies_in(neighbor_key..=neighbor_key));
+                        }
+                    }
+                }
+            }
+        }
+
+        collisions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    fn random_spheres(seed: u64, n: usize, extent: f32, max_radius: f32) -> (Vec<Vec3A>, Vec<f32>) {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        (0..n)
+            .map(|_| {
+                let pos = Vec3A::new(
+                    rng.random_range(-extent..extent),
+                    rng.random_range(-extent..extent),
+                    rng.random_range(-extent..extent),
+                );
+                (pos, rng.random_range(0.0..max_radius))
+            })
+            .unzip()
+    }
+
+    fn sorted(mut pairs: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+        pairs.sort_unstable();
+        pairs
+    }
+
+    #[test]
+    fn broadphases_match_brute_force() {
+        let broadphases: [(&str, &dyn Broadphase); 3] = [
+            ("sweep and prune", &SweepAndPrune),
+            ("grid", &SpatialHashGrid::default()),
+            (
+                "grid with small cells",
+                &SpatialHashGrid {
+                    cell_size: Some(0.1),
+                },
+            ),
+        ];
+
+        for (seed, n, extent, max_radius) in
+            [(1, 10, 5.0, 1.0), (2, 500, 100.0, 4.0), (3, 800, 20.0, 0.5)]
+        {
+            let (positions, radii) = random_spheres(seed, n, extent, max_radius);
+            let expected = sorted(BruteForce.find_collisions(&positions, &radii));
+            assert!(!expected.is_empty(), "Test case should contain collisions");
+
+            for (name, broadphase) in broadphases {
+                let found = sorted(broadphase.find_collisions(&positions, &radii));
+                assert_eq!(
+                    found, expected,
+                    "{name} differs from brute force (seed {seed})"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn grid_handles_zero_radii() {
+        let (positions, _) = random_spheres(4, 1000, 1.0e4, 1.0);
+        let radii = vec![0.0; positions.len()];
+
+        // Without a floor, the cells would shrink to `f32::EPSILON`
+        let grid = SpatialHashGrid::default();
+        let cell_size = grid.cell_size_for(&positions, &radii);
+        assert!(cell_size > 1.0e3, "Cell size {cell_size} is too small");
+
+        assert!(grid.find_collisions(&positions, &radii).is_empty());
+        assert!(SweepAndPrune.find_collisions(&positions, &radii).is_empty());
+    }
+
+    #[test]
+    fn grid_handles_cells_beyond_i32() {
+        // Cell coordinates saturate at the limits of `i32`
+        let positions = [
+            Vec3A::splat(1.0e12),
+            Vec3A::splat(1.0e12),
+            Vec3A::splat(-1.0e12),
+            Vec3A::splat(-1.0e12),
+            Vec3A::ZERO,
+        ];
+        let radii = [1.0; 5];
+
+        let expected = sorted(BruteForce.find_collisions(&positions, &radii));
+        assert_eq!(expected, [(0, 1), (2, 3)]);
+
+        let grid = SpatialHashGrid {
+            cell_size: Some(1.0),
+        };
+        assert_eq!(sorted(grid.find_collisions(&positions, &radii)), expected);
+    }
+}
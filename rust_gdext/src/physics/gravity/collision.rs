@@ -0,0 +1,254 @@
+//! Resolution of collisions between simulated bodies.
+//!
+//! Collision detection produces a list of colliding index pairs. Pairs that share a body
+//! belong to the same physical pile-up, so they are grouped into connected clusters using a
+//! [`DisjointSet`] and each cluster is merged into a single body in one step. This keeps the
+//! result independent of the order in which the pairs were detected.
+
+use super::{
+    HasMass, HasPosition, HasRadius, HasVelocity, RadiusMass, controller::SimulatedBody,
+    heuristic_radius, rotation::moment_of_inertia,
+};
+use glam::Vec3A;
+
+/// Union-find structure over the indices `0..n`.
+///
+/// Uses path halving and union by rank, giving effectively constant time operations.
+pub struct DisjointSet {
+    parent: Vec<usize>,
+    rank: Vec<u8>,
+}
+
+impl DisjointSet {
+    /// Creates a set where every index is its own singleton cluster.
+    pub fn new(n: usize) -> Self {
+        Self {
+            parent: (0..n).collect(),
+            rank: vec![0; n],
+        }
+    }
+
+    /// Builds a set of `n` indices where every pair in `pairs` is joined.
+    pub fn from_pairs(n: usize, pairs: impl IntoIterator<Item = (usize, usize)>) -> Self {
+        let mut set = Self::new(n);
+        for (a, b) in pairs {
+            set.union(a, b);
+        }
+        set
+    }
+
+    /// Returns the representative of the cluster containing `x`.
+    pub fn find(&mut self, mut x: usize) -> usize {
+        while self.parent[x] != x {
+            // Path halving: point every other node on the path to its grandparent
+            self.parent[x] = self.parent[self.parent[x]];
+            x = self.parent[x];
+        }
+        x
+    }
+
+    /// Joins the clusters containing `a` and `b`.
+    pub fn union(&mut self, a: usize, b: usize) {
+        let (root_a, root_b) = (self.find(a), self.find(b));
+        if root_a == root_b {
+            return;
+        }
+
+        match self.rank[root_a].cmp(&self.rank[root_b]) {
+            std::cmp::Ordering::Less => self.parent[root_a] = root_b,
+            std::cmp::Ordering::Greater => self.parent[root_b] = root_a,
+            std::cmp::Ordering::Equal => {
+                self.parent[root_b] = root_a;
+                self.rank[root_a] += 1;
+            }
+        }
+    }
+
+    /// Returns every cluster with more than one member.
+    ///
+    /// Clusters are ordered by their smallest index and members are sorted ascending,
+    /// so the result is deterministic for a given set of unions.
+    pub fn clusters(&mut self) -> Vec<Vec<usize>> {
+        let n = self.parent.len();
+        let mut cluster_of_root = vec![None; n];
+        let mut clusters: Vec<Vec<usize>> = Vec::new();
+
+        for i in 0..n {
+            let root = self.find(i);
+            let cluster_idx = *cluster_of_root[root].get_or_insert_with(|| {
+                clusters.push(Vec::new());
+                clusters.len() - 1
+            });
+            clusters[cluster_idx].push(i);
+        }
+
+        clusters.retain(|c| c.len() > 1);
+        clusters
+    }
+}
+
+/// Merges a cluster of colliding bodies into a single body.
+///
+/// The merged body:
+/// - keeps the identity of the heaviest member (the lowest index wins ties). Pinned and
+///   kinematic members take precedence over dynamic ones, as they can't be moved,
+/// - has the total mass of the cluster,
+/// - sits at the combined center of mass and moves with the center-of-mass velocity,
+/// - spins with the orbital angular momentum of the members around their common
+///   center of mass plus their own spin angular momentum, treating it as a uniform sphere,
+/// - has the radius of a sphere with the combined volume of the members, if any member
+///   has a physical radius. Members without one contribute their heuristic radius.
+///
+/// Mass, linear momentum, angular momentum and volume are therefore all conserved, unless
+/// the survivor is pinned or kinematic. It then keeps its own position and velocity.
+///
+/// # Returns
+///
+/// The index of the surviving body within `bodies`, and its merged state.
+pub fn merge_cluster(
+    bodies: &[SimulatedBody],
+    cluster: &[usize],
+    merge_scaler: f32,
+) -> (usize, SimulatedBody) {
+    debug_assert!(!cluster.is_empty(), "Cannot merge an empty cluster");
+
+    let survivor_idx = cluster
+        .iter()
+        .copied()
+        .reduce(|best, i| {
+            let key = |b: &SimulatedBody| (!b.motion.is_dynamic(), b.get_mass());
+            if key(&bodies[i]) > key(&bodies[best]) {
+                i
+            } else {
+                best
+            }
+        })
+        .expect("Cluster should not be empty");
+
+    let members = || cluster.iter().map(|&i| &bodies[i]);
+
+    let total_mass: f32 = members().map(HasMass::get_mass).sum();
+
+    // Fall back to unweighted averages for massless clusters
+    let weight = |b: &SimulatedBody| {
+        if total_mass > 0.0 {
+            b.get_mass() / total_mass
+        } else {
+            1.0 / cluster.len() as f32
+        }
+    };
+
+    let com_pos: Vec3A = members().map(|b| b.get_pos() * weight(b)).sum();
+    let com_vel: Vec3A = members().map(|b| b.get_vel() * weight(b)).sum();
+
+    // Orbital angular momentum around the common center of mass becomes spin
+    let angular_momentum: Vec3A = members()
+        .map(|b| {
+            let inertia = moment_of_inertia(b.get_mass(), b.collision_radius(merge_scaler));
+            inertia * b.angular_velocity
+                + b.get_mass() * (b.get_pos() - com_pos).cross(b.get_vel() - com_vel)
+        })
+        .sum();
+
+    let radius = members().any(|b| b.get_radius().is_some()).then(|| {
+        members()
+            .map(|b| b.collision_radius(merge_scaler).powi(3))
+            .sum::<f32>()
+            .cbrt()
+    });
+
+    let merged_radius = radius.unwrap_or_else(|| heuristic_radius(merge_scaler, total_mass));
+    let merged_inertia = moment_of_inertia(total_mass, merged_radius);
+    let angular_velocity = if merged_inertia > 0.0 {
+        angular_momentum / merged_inertia
+    } else {
+        Vec3A::ZERO
+    };
+
+    let survivor = &bodies[survivor_idx];
+    let (pos, vel) = if survivor.motion.is_dynamic() {
+        (com_pos, com_vel)
+    } else {
+        (survivor.get_pos(), survivor.get_vel())
+    };
+
+    let merged = SimulatedBody {
+        mass: total_mass,
+        pos,
+        vel,
+        angular_velocity,
+        radius,
+        ..survivor.clone()
+    };
+
+    (survivor_idx, merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::gravity::motion::BodyMotion;
+
+    #[test]
+    fn chained_pairs_form_one_cluster() {
+        let mut set = DisjointSet::from_pairs(6, [(0, 2), (4, 5), (2, 3)]);
+
+        assert_eq!(set.clusters(), vec![vec![0, 2, 3], vec![4, 5]]);
+    }
+
+    #[test]
+    fn merge_conserves_momentum_and_angular_momentum() {
+        let bodies = vec![
+            SimulatedBody::test(1, 1.0, Vec3A::new(1.0, 0.0, 0.0), Vec3A::new(0.0, 1.0, 0.0)),
+            SimulatedBody::test(
+                2,
+                3.0,
+                Vec3A::new(-1.0, 0.0, 0.0),
+                Vec3A::new(0.0, -1.0, 0.5),
+            ),
+            SimulatedBody::test(3, 2.0, Vec3A::new(0.0, 1.0, 0.0), Vec3A::new(1.0, 0.0, 0.0)),
+        ];
+
+        let momentum: Vec3A = bodies.iter().map(|b| b.mass * b.vel).sum();
+        let angular_momentum: Vec3A = bodies.iter().map(|b| b.mass * b.pos.cross(b.vel)).sum();
+
+        let (survivor, merged) = merge_cluster(&bodies, &[0, 1, 2], 1.0);
+
+        assert_eq!(survivor, 1);
+        assert_eq!(merged.body_instance_id, bodies[1].body_instance_id);
+        assert_eq!(merged.mass, 6.0);
+        assert!((merged.mass * merged.vel).abs_diff_eq(momentum, 1e-5));
+
+        let inertia = moment_of_inertia(merged.mass, merged.collision_radius(1.0));
+        let merged_angular_momentum =
+            inertia * merged.angular_velocity + merged.mass * merged.pos.cross(merged.vel);
+        assert!(merged_angular_momentum.abs_diff_eq(angular_momentum, 1e-5));
+    }
+
+    #[test]
+    fn merge_conserves_volume() {
+        let mut a = SimulatedBody::test(1, 1.0, Vec3A::ZERO, Vec3A::ZERO);
+        let mut b = SimulatedBody::test(2, 1.0, Vec3A::X, Vec3A::ZERO);
+        a.radius = Some(3.0);
+        b.radius = Some(4.0);
+
+        let (_, merged) = merge_cluster(&[a, b], &[0, 1], 1.0);
+
+        let expected = (3.0f32.powi(3) + 4.0f32.powi(3)).cbrt();
+        assert!((merged.radius.unwrap() - expected).abs() < 1e-5);
+    }
+
+    #[test]
+    fn pinned_body_survives_in_place() {
+        let mut pinned = SimulatedBody::test(1, 1.0, Vec3A::ZERO, Vec3A::ZERO);
+        pinned.motion = BodyMotion::Pinned;
+        let heavy = SimulatedBody::test(2, 10.0, Vec3A::X, Vec3A::Y);
+
+        let (survivor, merged) = merge_cluster(&[pinned, heavy], &[0, 1], 1.0);
+
+        assert_eq!(survivor, 0);
+        assert_eq!(merged.mass, 11.0);
+        assert_eq!(merged.pos, Vec3A::ZERO);
+        assert_eq!(merged.vel, Vec3A::ZERO);
+    }
+}
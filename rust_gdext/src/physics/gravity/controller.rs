@@ -1,21 +1,49 @@
-use std::collections::HashSet;
+pub mod trajectories;
 
 use super::{
-    HasMass, HasPosition, HasVelocity, NBodyGravityCalculator, body::GravityBody,
-    direct_summation::DirectSummation, trajectories::TrajectoryWorker,
+    HasMass, HasOblateness, HasPosition, HasRadius, HasVelocity, NBodyGravityCalculator,
+    RadiusMass,
+    body::GravityBody,
+    broadphase::{Broadphase, BruteForce, SpatialHashGrid, SweepAndPrune},
+    collision::{DisjointSet, merge_cluster},
+    direct_summation::DirectSummation,
+    encounter::Encounter,
+    field::accelerations_at,
+    forces::{
+        AtmosphericDrag, CallableForce, ConstantThrust, ForceGenerator, PostNewtonianCorrection,
+        RadiationPressure,
+    },
+    frame::TrajectoryFrame,
+    harmonics::ZonalHarmonics,
+    hierarchy::{InfluenceSphere, OrbitalHierarchy},
+    lagrange::{lagrange_points, refine_lagrange_points},
+    maneuver::PlannedManeuver,
+    motion::{BodyMotion, rails_order},
+    orbit::propagate_kepler,
+    rotation::{apply_tidal_locking, integrate_orientation},
+    test_particles::{TestParticle, step_test_particles},
+    tidal::{Debris, detect_disruptions, disrupt},
 };
 use crate::{
+    from_glam_vec3,
     octree::{morton_based::MortonBasedOctree, visualize::OctreeVisualizer},
-    physics::gravity::VelMass,
-    to_glam_vec3,
+    to_glam_quat, to_glam_vec3,
+    worker::CancellationToken,
 };
-use glam::Vec3A;
+use glam::{Quat, Vec3A};
 use godot::{
     classes::{MeshInstance3D, notify::Node3DNotification},
     prelude::*,
 };
 use itertools::Itertools;
 use proc::editor;
+use trajectories::{MergeEvent, TrajectoryWorker};
+
+/// Body counts at which the collision broadphase switches from brute force to
+/// sweep-and-prune, and then to a spatial hash grid. These are rough estimates, compare
+/// the `collision_broadphase` benchmark on the target hardware when tuning them.
+const BRUTE_FORCE_MAX: usize = 32;
+const SWEEP_AND_PRUNE_MAX: usize = 50_000;
 
 /// Manages gravity interactions between [`GravityBody`] instances in a 3D space.
 ///
@@ -28,7 +56,7 @@ use proc::editor;
 ///
 /// # Related Modules
 ///
-/// See the [`trajectories`](super::trajectories) module for details on trajectory visualization.
+/// See the [`trajectories`] module for details on trajectory visualization.
 ///
 /// # Example
 ///
@@ -55,13 +83,95 @@ pub struct GravityController {
     #[init(val = true)]
     pub merge_on_collision: bool,
 
+    /// Scales the mass-based collision radius of bodies without a physical radius
     #[export]
     #[init(val = 12.0)]
     pub merge_scaler: f32,
 
+    /// Whether to add the first post-Newtonian correction of general relativity between
+    /// massive bodies, which makes orbits around compact objects precess
+    #[export]
+    pub post_newtonian: bool,
+
+    /// Speed of light in simulation units. Lower values exaggerate relativistic effects
+    #[export]
+    #[init(val = 1000.0)]
+    pub speed_of_light: f32,
+
+    /// Minimum mass of both bodies of a pair for the post-Newtonian correction to apply
+    #[export]
+    #[init(val = 100.0)]
+    pub post_newtonian_mass_threshold: f32,
+
+    /// Whether bodies inside the Roche limit of a heavier body are torn apart into debris.
+    /// Only bodies with a physical radius or density can be disrupted.
+    #[export]
+    #[init(val = true)]
+    pub tidal_disruption: bool,
+
+    /// How fast tides lock the rotation of moons to their orbit, 0 disables tidal locking.
+    /// Only bodies with a physical radius or density are locked
+    #[export]
+    pub tidal_locking: f32,
+
+    /// Number of debris fragments a tidally disrupted body breaks into
+    #[export]
+    #[init(val = 128)]
+    pub debris_per_disruption: u32,
+
+    /// Whether the debris of tidal disruptions is added to the test particles
+    #[export]
+    #[init(val = true)]
+    pub debris_as_test_particles: bool,
+
+    /// Eccentricity of the orbits set up by `assign_orbital_velocities`,
+    /// 0 for circular orbits
+    #[export]
+    pub orbit_eccentricity: f32,
+
+    /// Whether `assign_orbital_velocities` also removes the net momentum of the system,
+    /// so it doesn't drift away
+    #[export]
+    pub zero_net_momentum: bool,
+
+    /// Sphere deciding the primary of every body in the orbital hierarchy
+    #[export]
+    pub hierarchy_sphere: InfluenceSphere,
+
+    /// Simulated seconds between updates of the orbital hierarchy, 0 updates it every
+    /// physics step. Updates are skipped while nothing is connected to `primary_changed`,
+    /// queries like `get_primary` bring the hierarchy up to date themselves
+    #[export]
+    #[init(val = 0.5)]
+    pub hierarchy_update_interval: f64,
+
+    /// Bodies on rails closer than `rails_focus_distance` to this node are integrated
+    /// instead, so only the bodies near the player pay for full n-body integration
+    #[export]
+    pub rails_focus: Option<Gd<Node3D>>,
+
+    /// Distance from `rails_focus` within which bodies on rails are integrated
+    #[export]
+    pub rails_focus_distance: f32,
+
+    /// Simulated time in seconds, advanced every physics step.
+    /// Kinematic bodies follow their paths according to it.
+    #[var]
+    pub sim_time: f64,
+
     /// Collection of all gravity bodies managed by this controller
     pub bodies: Vec<Gd<GravityBody>>,
 
+    /// Who orbits whom, indexed like `bodies`, see `hierarchy_update_interval`
+    pub hierarchy: OrbitalHierarchy,
+
+    /// Simulated time of the last update of `hierarchy`, `None` before the first one
+    pub hierarchy_time: Option<f64>,
+
+    /// Massless particles that are attracted by the bodies but don't attract anything,
+    /// see [`test_particles`](super::test_particles)
+    pub test_particles: Vec<TestParticle>,
+
     /// Number of steps to compute when simulating trajectories
     #[export]
     #[init(val = 4000)]
@@ -72,6 +182,39 @@ pub struct GravityController {
     #[init(val = 0.3)]
     pub simulation_step_delta: f32,
 
+    /// Maximum number of vertices per displayed trajectory, 0 for no limit.
+    /// Points are removed where the path is straightest first
+    #[export]
+    #[init(val = 1000)]
+    pub trajectory_vertex_budget: u32,
+
+    /// Trajectory points deviating less than this from the simplified path are removed,
+    /// even when the trajectory fits the vertex budget
+    #[export]
+    #[init(val = 0.01)]
+    pub trajectory_decimation_tolerance: f32,
+
+    /// Opacity of trajectories at the end of the prediction, relative to their start.
+    /// Trajectories fade linearly in between, 1 disables fading
+    #[export(range = (0.0, 1.0))]
+    #[init(val = 0.15)]
+    pub trajectory_end_alpha: f32,
+
+    /// Width of trajectories drawn as camera-facing ribbons, 0 draws thin lines
+    #[export]
+    pub trajectory_ribbon_width: f32,
+
+    /// Prediction time between tick marks along trajectories, which are spaced further
+    /// apart where bodies move faster. Widened to draw at most 1000 ticks per trajectory,
+    /// 0 disables tick marks
+    #[export]
+    pub trajectory_tick_interval: f32,
+
+    /// Length of the tick marks across trajectories
+    #[export]
+    #[init(val = 1.0)]
+    pub trajectory_tick_size: f32,
+
     /// Whether to automatically recalculate trajectories on certain changes
     #[export]
     #[init(val = true)]
@@ -79,15 +222,81 @@ pub struct GravityController {
 
     pub trajectory_worker: Option<TrajectoryWorker>,
 
-    /// Optional body to use as the reference point for trajectory calculations
+    /// Cancels the calculation running on the trajectory worker, if any
+    pub trajectory_cancel: Option<CancellationToken>,
+
+    /// Number of queued trajectory calculations, results of earlier ones are stale
+    pub trajectory_generation: u64,
+
+    /// Number of simulated steps between partial results of the trajectory worker,
+    /// 0 to only show finished predictions
+    #[export]
+    #[init(val = 500)]
+    pub trajectory_progress_interval: u32,
+
+    /// Whether the displayed trajectories are a finished prediction
+    pub trajectories_complete: bool,
+
+    /// Whether trajectories are also predicted for the test particles
+    #[export]
+    pub predict_test_particles: bool,
+
+    /// Color of the predicted test particle trajectories
+    #[export]
+    #[init(val = Color::from_rgba(0.6, 0.6, 0.6, 1.0))]
+    pub test_particle_trajectory_color: Color,
+
+    /// Body whose closest approaches to `encounter_target` are predicted and marked
+    #[export]
+    pub encounter_body: Option<Gd<GravityBody>>,
+
+    /// Body approached by `encounter_body`, or none to search encounters with all bodies
+    #[export]
+    pub encounter_target: Option<Gd<GravityBody>>,
+
+    /// Size of the crosses marking both bodies at an encounter
+    #[export]
+    #[init(val = 1.0)]
+    pub encounter_marker_size: f32,
+
+    #[export]
+    #[init(val = Color::from_rgba(1.0, 0.9, 0.3, 1.0))]
+    pub encounter_marker_color: Color,
+
+    /// Encounters of the displayed prediction, ordered by time
+    pub encounters: Vec<Encounter>,
+
+    /// Size of the markers at predicted collisions
+    #[export]
+    #[init(val = 2.0)]
+    pub impact_marker_size: f32,
+
+    #[export]
+    #[init(val = Color::from_rgba(1.0, 0.3, 0.2, 1.0))]
+    pub impact_marker_color: Color,
+
+    /// Collisions of the displayed prediction, ordered by time
+    pub predicted_merges: Vec<MergeEvent>,
+
+    /// Frame that trajectories are drawn in, relative to `sim_center_body` and
+    /// `frame_secondary_body`
+    #[export]
+    pub trajectory_frame: TrajectoryFrame,
+
+    /// Body the trajectory frame is centered on. Frames need it to be anything but inertial
     #[export]
     pub sim_center_body: Option<Gd<GravityBody>>,
 
+    /// Second body of the barycentric and synodic frames. Without it, they are centered
+    /// on `sim_center_body`
+    #[export]
+    pub frame_secondary_body: Option<Gd<GravityBody>>,
+
     #[export]
     pub octree_visualizer: Option<Gd<OctreeVisualizer>>,
 
-    /// Mesh instances representing the currently displayed trajectories
-    pub trajectories: Vec<Gd<MeshInstance3D>>,
+    /// Mesh instance displaying all trajectories, created once there is something to draw
+    pub trajectory_instance: Option<Gd<MeshInstance3D>>,
 }
 
 /// Simulated representation of a [`GravityBody`] for physics calculations.
@@ -114,6 +323,41 @@ pub struct SimulatedBody {
 
     /// Current velocity vector
     pub vel: Vec3A,
+
+    /// Current rotation of the body
+    pub orientation: Quat,
+
+    /// Angular velocity in radians per second, around the axis it points along
+    pub angular_velocity: Vec3A,
+
+    /// Physical radius, if known. Collisions fall back to a mass-based heuristic otherwise
+    pub radius: Option<f32>,
+
+    /// Zonal harmonic coefficients `J2`, `J3` and `J4` of the gravity field,
+    /// relative to the physical radius
+    pub zonal_harmonics: [f32; 3],
+
+    /// Whether the body is integrated, pinned, or moved kinematically
+    pub motion: BodyMotion,
+}
+
+#[cfg(test)]
+impl SimulatedBody {
+    /// A dynamic point mass without rotation, for tests. Other fields can be overridden
+    /// with `SimulatedBody { radius: Some(1.0), ..SimulatedBody::test(..) }`.
+    pub fn test(id: i64, mass: f32, pos: Vec3A, vel: Vec3A) -> Self {
+        Self {
+            body_instance_id: InstanceId::from_i64(id),
+            mass,
+            pos,
+            vel,
+            orientation: Quat::IDENTITY,
+            angular_velocity: Vec3A::ZERO,
+            radius: None,
+            zonal_harmonics: [0.0; 3],
+            motion: BodyMotion::Dynamic,
+        }
+    }
 }
 
 impl HasPosition for SimulatedBody {
@@ -152,6 +396,62 @@ impl HasMass for SimulatedBody {
     }
 }
 
+impl HasOblateness for SimulatedBody {
+    /// Uses the rotation axis, or the local Y axis for bodies that don't rotate.
+    /// Bodies without a physical radius are point masses.
+    #[inline]
+    fn get_zonal_harmonics(&self) -> Option<ZonalHarmonics> {
+        if self.zonal_harmonics == [0.0; 3] {
+            return None;
+        }
+
+        Some(ZonalHarmonics {
+            radius: self.radius?,
+            axis: self
+                .angular_velocity
+                .try_normalize()
+                .unwrap_or_else(|| self.orientation * Vec3A::Y),
+            coefficients: self.zonal_harmonics,
+        })
+    }
+}
+
+impl HasRadius for SimulatedBody {
+    #[inline(always)]
+    fn get_radius(&self) -> Option<f32> {
+        self.radius
+    }
+
+    #[inline(always)]
+    fn set_radius(&mut self, radius: Option<f32>) {
+        self.radius = radius;
+    }
+}
+
+/// Colliding bodies that merged into one.
+pub struct BodyMerge {
+    /// The body that absorbed the others
+    pub survivor: InstanceId,
+
+    /// The bodies that were absorbed and removed
+    pub absorbed: Vec<InstanceId>,
+
+    /// Position of the merged body
+    pub pos: Vec3A,
+}
+
+/// A body torn apart by the tides of its primary.
+pub struct TidalDisruption {
+    /// The body that was torn apart
+    pub satellite: InstanceId,
+
+    /// The body whose tides tore it apart
+    pub primary: InstanceId,
+
+    /// The debris the satellite broke into
+    pub debris: Debris,
+}
+
 /// Converts a gravity body node reference into its simulation representation.
 ///
 /// This implementation provides a clean way to extract the essential physical properties
@@ -160,12 +460,21 @@ impl From<&Gd<GravityBody>> for SimulatedBody {
     #[inline]
     fn from(body: &Gd<GravityBody>) -> Self {
         let b = body.bind();
+        let motion = b.motion();
 
         Self {
             body_instance_id: body.instance_id(),
             mass: b.mass,
-            vel: to_glam_vec3(b.velocity),
+            vel: match motion {
+                BodyMotion::Pinned => Vec3A::ZERO,
+                _ => to_glam_vec3(b.velocity),
+            },
             pos: to_glam_vec3(body.get_position()),
+            orientation: to_glam_quat(body.get_quaternion()),
+            angular_velocity: to_glam_vec3(b.angular_velocity),
+            radius: b.resolved_radius(),
+            zonal_harmonics: [b.j2, b.j3, b.j4],
+            motion,
         }
     }
 }
@@ -200,6 +509,8 @@ impl GravityController {
     fn get_gravity_bodies(&mut self) {
         fn collect_bodies_rec(node: Gd<Node>, bodies: &mut Vec<Gd<GravityBody>>) {
             match node.try_cast::<GravityBody>() {
+                // Skip bodies that have been merged or disrupted but not yet freed
+                Ok(body) if body.is_queued_for_deletion() => {}
                 Ok(body) => bodies.push(body),
                 Err(node) => {
                     node.get_children()
@@ -222,19 +533,32 @@ impl GravityController {
     ///
     /// This method:
     /// 1. Calculates acceleration for each body in parallel
-    /// 2. Updates velocities based on the calculated accelerations
-    /// 3. Updates positions based on the new velocities
+    /// 2. Adds the accelerations of non-gravitational forces
+    /// 3. Updates velocities based on the calculated accelerations
+    /// 4. Updates positions based on the new velocities
+    /// 5. Rotates bodies by their angular velocity
+    ///
+    /// Only dynamic bodies are integrated. Pinned bodies stay in place, kinematic bodies
+    /// move to the point of their path at the end of the step, and bodies on rails move
+    /// along the Kepler orbit around their parent, after the parent has moved.
     ///
     /// Uses parallel processing through Rayon to optimize performance for many bodies.
     ///
     /// # Parameters
     /// - `grav_const`: The gravitational constant to use in calculations
     /// - `delta`: The time step duration in seconds
+    /// - `time`: The simulation time at the start of the step
     /// - `bodies_sim`: The bodies to simulate, will be updated in-place
-    /// - `accelerations`: A reusable buffer for storing the calculated accelerations
-    pub fn step_time(grav_const: f32, delta: f32, bodies_sim: &mut [SimulatedBody]) {
+    /// - `forces`: Non-gravitational forces acting on the bodies
+    pub fn step_time(
+        grav_const: f32,
+        delta: f32,
+        time: f64,
+        bodies_sim: &mut [SimulatedBody],
+        forces: &[&dyn ForceGenerator],
+    ) {
         // 1: Calculate accelerations
-        let accelerations = match bodies_sim.len() {
+        let mut accelerations = match bodies_sim.len() {
             // Thresholds are benchmarked
             //          Algorithm                  Parallel
             ..100 => DirectSummation::new(bodies_sim).calc_accs::<false>(grav_const),
@@ -242,43 +566,453 @@ impl GravityController {
             440.. => MortonBasedOctree::new(bodies_sim).calc_accs::<true>(grav_const),
         };
 
-        // 2: Update velocities and positions
+        // 2: Add non-gravitational forces
+        for force in forces {
+            force.add_accelerations(bodies_sim, &mut accelerations);
+        }
+
+        // Bodies on rails whose parent can't be resolved are integrated
+        let rails = rails_order(bodies_sim);
+        let mut on_rails = vec![false; bodies_sim.len()];
+        rails.iter().for_each(|&(i, _)| on_rails[i] = true);
+        let start_states = if rails.is_empty() {
+            Vec::new()
+        } else {
+            bodies_sim.iter().map(|b| (b.pos, b.vel)).collect_vec()
+        };
+
+        // 3: Update velocities and positions
         bodies_sim
             .iter_mut()
             .zip(accelerations)
-            .for_each(|(body, acc)| {
-                body.vel += acc * delta;
-                body.pos += body.vel * delta;
+            .enumerate()
+            .for_each(|(i, (body, acc))| match &body.motion {
+                BodyMotion::OnRails(_) if on_rails[i] => {}
+                BodyMotion::Dynamic | BodyMotion::OnRails(_) => {
+                    body.vel += acc * delta;
+                    body.pos += body.vel * delta;
+                }
+                BodyMotion::Kinematic(Some(path)) => {
+                    let pos = path.sample(time + f64::from(delta));
+                    body.vel = (pos - body.pos) / delta;
+                    body.pos = pos;
+                }
+                BodyMotion::Pinned | BodyMotion::Kinematic(None) => {}
             });
+
+        // 4: Move bodies on rails relative to their parents, which have already moved
+        for (i, parent) in rails {
+            let mu = grav_const * (bodies_sim[i].mass + bodies_sim[parent].mass);
+            let (rel_pos, rel_vel) = propagate_kepler(
+                mu,
+                start_states[i].0 - start_states[parent].0,
+                start_states[i].1 - start_states[parent].1,
+                delta,
+            );
+
+            bodies_sim[i].pos = bodies_sim[parent].pos + rel_pos;
+            bodies_sim[i].vel = bodies_sim[parent].vel + rel_vel;
+        }
+
+        // 5: Rotate bodies
+        for body in bodies_sim.iter_mut() {
+            body.orientation =
+                integrate_orientation(body.orientation, body.angular_velocity, delta);
+        }
     }
 
-    pub fn merge_bodies(merge_scaler: f32, bodies_sim: &mut Vec<SimulatedBody>) -> Vec<InstanceId> {
+    /// Creates the simulation counterparts of all bodies.
+    ///
+    /// Bodies on rails within `rails_focus_distance` of the `rails_focus` are integrated.
+    pub fn simulated_bodies(&self) -> Vec<SimulatedBody> {
+        let focus = self
+            .rails_focus
+            .as_ref()
+            .filter(|_| self.rails_focus_distance > 0.0)
+            .map(|f| f.get_global_position());
+
+        self.bodies
+            .iter()
+            .map(|body| {
+                let mut sim = SimulatedBody::from(body);
+
+                if let (BodyMotion::OnRails(_), Some(focus)) = (&sim.motion, focus)
+                    && body.get_global_position().distance_to(focus) < self.rails_focus_distance
+                {
+                    sim.motion = BodyMotion::Dynamic;
+                }
+
+                sim
+            })
+            .collect()
+    }
+
+    /// Collects the non-gravitational forces defined by the properties of the bodies.
+    ///
+    /// Forces from a body's `force_callable` are not included, as they can only be
+    /// evaluated on the main thread. See [`Self::callable_forces`].
+    pub fn force_generators(&self) -> Vec<Box<dyn ForceGenerator + Send + Sync>> {
+        let mut generators: Vec<Box<dyn ForceGenerator + Send + Sync>> = Vec::new();
+
+        if self.post_newtonian {
+            generators.push(Box::new(PostNewtonianCorrection {
+                grav_const: self.grav_const,
+                speed_of_light: self.speed_of_light,
+                mass_threshold: self.post_newtonian_mass_threshold,
+            }));
+        }
+
+        for body in &self.bodies {
+            let id = body.instance_id();
+            let b = body.bind();
+
+            if b.thrust != Vector3::ZERO {
+                generators.push(Box::new(ConstantThrust {
+                    body: id,
+                    force: to_glam_vec3(b.thrust),
+                }));
+            }
+
+            if b.drag_range > 0.0 && (b.linear_drag != 0.0 || b.quadratic_drag != 0.0) {
+                generators.push(Box::new(AtmosphericDrag {
+                    source: id,
+                    range: b.drag_range,
+                    linear: b.linear_drag,
+                    quadratic: b.quadratic_drag,
+                }));
+            }
+
+            if b.luminosity > 0.0 {
+                let targets = self
+                    .bodies
+                    .iter()
+                    .filter(|t| t.instance_id() != id)
+                    .map(|t| (t.instance_id(), t.bind().radiation_pressure_coefficient))
+                    .filter(|&(_, coefficient)| coefficient != 0.0)
+                    .collect::<Vec<_>>();
+
+                if !targets.is_empty() {
+                    generators.push(Box::new(RadiationPressure {
+                        source: id,
+                        luminosity: b.luminosity,
+                        targets,
+                    }));
+                }
+            }
+        }
+
+        generators
+    }
+
+    /// Calculates the gravitational accelerations of all bodies at arbitrary points.
+    pub fn sample_accelerations_at(&self, points: &[Vec3A]) -> Vec<Vec3A> {
+        if self.bodies.is_empty() {
+            return vec![Vec3A::ZERO; points.len()];
+        }
+
+        accelerations_at(self.grav_const, &self.simulated_bodies(), points)
+    }
+
+    /// Computes L1 to L5 of `secondary` orbiting `primary`, see [`lagrange`](super::lagrange).
+    ///
+    /// With `refine`, the points are corrected against the gravity of all bodies.
+    /// Returns `None` if either body isn't managed by this controller.
+    pub fn lagrange_points(
+        &self,
+        primary: &Gd<GravityBody>,
+        secondary: &Gd<GravityBody>,
+        refine: bool,
+    ) -> Option<[Vec3A; 5]> {
+        let bodies_sim = self.simulated_bodies();
+        let p = self.bodies.iter().position(|b| b == primary)?;
+        let s = self.bodies.iter().position(|b| b == secondary)?;
+
+        let mut points = lagrange_points(&bodies_sim[p], &bodies_sim[s])?;
+        if refine {
+            refine_lagrange_points(self.grav_const, &bodies_sim, p, s, &mut points);
+        }
+
+        Some(points)
+    }
+
+    /// Updates the orbital hierarchy and emits `primary_changed` for every body that
+    /// moved to another primary.
+    pub fn update_hierarchy(&mut self, bodies_sim: &[SimulatedBody]) {
+        let changed = self
+            .hierarchy
+            .update(self.grav_const, self.hierarchy_sphere, bodies_sim);
+        self.hierarchy_time = Some(self.sim_time);
+
+        let changes = changed
+            .into_iter()
+            .filter_map(|i| {
+                let node = &self.hierarchy.nodes[i];
+                let body = self.body_by_id(node.body)?;
+                Some((body, node.parent_body.and_then(|id| self.body_by_id(id))))
+            })
+            .collect_vec();
+
+        for (body, primary) in changes {
+            self.base_mut().emit_signal(
+                "primary_changed",
+                &[body.to_variant(), primary.to_variant()],
+            );
+        }
+    }
+
+    /// Whether `hierarchy_update_interval` has passed since the last hierarchy update.
+    fn hierarchy_due(&self) -> bool {
+        self.hierarchy_time
+            .is_none_or(|time| self.sim_time - time >= self.hierarchy_update_interval)
+    }
+
+    /// Returns the managed body with the given instance ID.
+    pub fn body_by_id(&self, id: InstanceId) -> Option<Gd<GravityBody>> {
+        self.bodies.iter().find(|b| b.instance_id() == id).cloned()
+    }
+
+    /// Collects the maneuvers of all bodies, ordered by time.
+    ///
+    /// Orbital frames are relative to the primaries in `hierarchy`, which is indexed like
+    /// the bodies.
+    pub fn planned_maneuvers(&self, hierarchy: &OrbitalHierarchy) -> Vec<PlannedManeuver> {
+        self.bodies
+            .iter()
+            .enumerate()
+            .flat_map(|(i, body)| {
+                let primary = hierarchy.primary_of(i, body.instance_id());
+                body.bind()
+                    .maneuvers
+                    .iter()
+                    .map(|&maneuver| PlannedManeuver {
+                        body: body.instance_id(),
+                        primary,
+                        maneuver,
+                    })
+                    .collect_vec()
+            })
+            .sorted_by(|a, b| a.maneuver.time.total_cmp(&b.maneuver.time))
+            .collect()
+    }
+
+    /// Removes the maneuvers due before `until` from the bodies, ordered by time.
+    ///
+    /// Maneuvers planned in the past are due immediately.
+    fn take_due_maneuvers(&mut self, until: f64) -> Vec<PlannedManeuver> {
+        let mut due = Vec::new();
+
+        let any_due = self.bodies.iter().any(|body| {
+            body.bind()
+                .maneuvers
+                .first()
+                .is_some_and(|m| m.time < until)
+        });
+        if !any_due {
+            return due;
+        }
+
+        // Orbital frames need the current primaries
+        self.refresh_hierarchy();
+
+        for i in 0..self.bodies.len() {
+            let primary = self.primary_id(i);
+            let mut body = self.bodies[i].clone();
+            let id = body.instance_id();
+
+            let mut body = body.bind_mut();
+            let count = body.maneuvers.partition_point(|m| m.time < until);
+            due.extend(
+                body.maneuvers
+                    .drain(..count)
+                    .map(|maneuver| PlannedManeuver {
+                        body: id,
+                        primary,
+                        maneuver,
+                    }),
+            );
+        }
+
+        due.sort_by(|a, b| a.maneuver.time.total_cmp(&b.maneuver.time));
+        due
+    }
+
+    /// Returns the primary of the body at index `i` if the hierarchy knows it.
+    pub fn primary_id(&self, i: usize) -> Option<InstanceId> {
+        self.hierarchy
+            .primary_of(i, self.bodies.get(i)?.instance_id())
+    }
+
+    /// Collects the forces computed by the bodies' `force_callable`s.
+    pub fn callable_forces(&self) -> Vec<CallableForce> {
+        self.bodies
+            .iter()
+            .filter_map(|body| {
+                let callable = body.bind().force_callable.clone();
+                callable.is_valid().then(|| CallableForce {
+                    body: body.instance_id(),
+                    callable,
+                })
+            })
+            .collect()
+    }
+
+    /// Detects collisions and merges every colliding cluster into a single body.
+    ///
+    /// Colliding pairs that share a body are grouped into connected clusters, and each
+    /// cluster is resolved in a single step using [`merge_cluster`]. This way a body that
+    /// has been merged away can never take part in another merge, and pile-ups of three
+    /// or more bodies resolve the same way regardless of the order of the pairs.
+    ///
+    /// # Returns
+    ///
+    /// The merges that took place. Absorbed bodies are removed from `bodies_sim`.
+    pub fn merge_bodies(merge_scaler: f32, bodies_sim: &mut Vec<SimulatedBody>) -> Vec<BodyMerge> {
+        let positions = bodies_sim.iter().map(HasPosition::get_pos).collect_vec();
+        let radii = bodies_sim
+            .iter()
+            .map(|b| b.collision_radius(merge_scaler))
+            .collect_vec();
+
         let collisions = match bodies_sim.len() {
-            ..440 => DirectSummation::new(bodies_sim).detect_collisions(merge_scaler),
-            440.. => MortonBasedOctree::new(bodies_sim).detect_collisions(merge_scaler),
+            ..BRUTE_FORCE_MAX => BruteForce.find_collisions(&positions, &radii),
+            BRUTE_FORCE_MAX..SWEEP_AND_PRUNE_MAX => {
+                SweepAndPrune.find_collisions(&positions, &radii)
+            }
+            SWEEP_AND_PRUNE_MAX.. => SpatialHashGrid::default().find_collisions(&positions, &radii),
         };
 
-        let mut instances_to_remove = HashSet::new();
+        if collisions.is_empty() {
+            return Vec::new();
+        }
 
-        for (idx_a, idx_b) in collisions {
-            // Keep the body with the higher mass, remove the other
-            let (keep_idx, remove_idx) = if bodies_sim[idx_a].mass >= bodies_sim[idx_b].mass {
-                (idx_a, idx_b)
-            } else {
-                (idx_b, idx_a)
-            };
+        let clusters = DisjointSet::from_pairs(bodies_sim.len(), collisions).clusters();
+
+        let mut remove = vec![false; bodies_sim.len()];
+        let mut merges = Vec::with_capacity(clusters.len());
+        for cluster in clusters {
+            let (survivor_idx, merged) = merge_cluster(bodies_sim, &cluster, merge_scaler);
 
-            let to_remove = &bodies_sim[remove_idx].clone();
+            let absorbed = cluster
+                .iter()
+                .filter(|&&i| i != survivor_idx)
+                .map(|&i| {
+                    remove[i] = true;
+                    bodies_sim[i].body_instance_id
+                })
+                .collect();
 
-            instances_to_remove.insert(to_remove.body_instance_id);
-            bodies_sim[keep_idx].non_elastic_collision(to_remove);
+            merges.push(BodyMerge {
+                survivor: merged.body_instance_id,
+                absorbed,
+                pos: merged.pos,
+            });
+            bodies_sim[survivor_idx] = merged;
+        }
+
+        let mut remove = remove.into_iter();
+        bodies_sim.retain(|_| !remove.next().unwrap_or(false));
+
+        merges
+    }
+
+    /// Tears apart every body that is inside the Roche limit of a heavier body.
+    ///
+    /// Disrupted bodies are removed from `bodies_sim` and replaced by a cloud of debris,
+    /// see [`disrupt`].
+    ///
+    /// # Returns
+    ///
+    /// The disruptions that took place, including the debris of each.
+    pub fn disrupt_bodies(
+        n_debris: usize,
+        bodies_sim: &mut Vec<SimulatedBody>,
+    ) -> Vec<TidalDisruption> {
+        let disruptions = detect_disruptions(bodies_sim);
+
+        if disruptions.is_empty() {
+            return Vec::new();
         }
 
-        bodies_sim.retain(|b| !instances_to_remove.contains(&b.body_instance_id));
-        instances_to_remove.into_iter().collect()
+        let events = disruptions
+            .iter()
+            .map(|d| {
+                let satellite = &bodies_sim[d.satellite];
+                let primary = &bodies_sim[d.primary];
+
+                TidalDisruption {
+                    satellite: satellite.body_instance_id,
+                    primary: primary.body_instance_id,
+                    debris: disrupt(satellite, n_debris),
+                }
+            })
+            .collect_vec();
+
+        bodies_sim.retain(|b| !events.iter().any(|e| e.satellite == b.body_instance_id));
+        events
+    }
+
+    /// Removes disrupted bodies from the scene and notifies listeners with the debris.
+    ///
+    /// If `debris_as_test_particles` is set, the debris is also added to the test particles.
+    fn apply_disruptions(&mut self, disruptions: Vec<TidalDisruption>) {
+        let find_body = |bodies: &[Gd<GravityBody>], id: InstanceId| {
+            bodies.iter().find(|b| b.instance_id() == id).cloned()
+        };
+
+        for disruption in disruptions {
+            let (Some(mut satellite), Some(primary)) = (
+                find_body(&self.bodies, disruption.satellite),
+                find_body(&self.bodies, disruption.primary),
+            ) else {
+                continue;
+            };
+
+            let (positions, velocities): (PackedVector3Array, PackedVector3Array) = disruption
+                .debris
+                .particles
+                .iter()
+                .map(|d| (from_glam_vec3(d.pos), from_glam_vec3(d.vel)))
+                .unzip();
+
+            self.base_mut().emit_signal(
+                "body_disrupted",
+                &[
+                    satellite.to_variant(),
+                    primary.to_variant(),
+                    positions.to_variant(),
+                    velocities.to_variant(),
+                ],
+            );
+
+            satellite.queue_free();
+
+            if self.debris_as_test_particles {
+                self.test_particles.extend(disruption.debris.particles);
+            }
+        }
     }
 }
 
+#[godot_api]
+impl GravityController {
+    /// Emitted when a body is torn apart by the tides of a heavier body.
+    ///
+    /// The disrupted body is freed afterwards. The debris starts as a cloud where the body
+    /// was, a ring visual can be spawned along the body's orbit around the primary.
+    #[signal]
+    fn body_disrupted(
+        body: Gd<GravityBody>,
+        primary: Gd<GravityBody>,
+        debris_positions: PackedVector3Array,
+        debris_velocities: PackedVector3Array,
+    );
+
+    /// Emitted when a body leaves the sphere of its primary or enters the sphere of
+    /// another body. `primary` is null when the body no longer orbits anything.
+    #[signal]
+    fn primary_changed(body: Gd<GravityBody>, primary: Option<Gd<GravityBody>>);
+}
+
 #[godot_api]
 impl INode3D for GravityController {
     /// Handles node notifications from the Godot engine.
@@ -298,30 +1032,62 @@ impl INode3D for GravityController {
     ///
     /// This method is called every physics frame by the Godot engine and:
     /// 1. Creates lightweight simulation counterparts for all managed bodies
-    /// 2. Simulates a single physics step using the current gravity constant
-    /// 3. Applies the simulation results back to the actual bodies in the scene
+    /// 2. Steps the test particles through the gravity field of the bodies
+    /// 3. Simulates a single physics step using the current gravity constant
+    /// 4. Applies the simulation results back to the actual bodies in the scene
     ///
-    /// If no bodies are present, this method returns early to avoid unnecessary processing.
+    /// If no bodies or test particles are present, this method returns early to avoid
+    /// unnecessary processing.
     ///
     /// # Parameters
     /// - `delta`: Time elapsed since the previous physics frame in seconds
     fn physics_process(&mut self, delta: f64) {
-        if self.bodies.is_empty() {
+        if self.bodies.is_empty() && self.test_particles.is_empty() {
             return;
         }
 
         // Create simulation counterparts of real bodies
-        let mut bodies_sim = self.bodies.iter().map(SimulatedBody::from).collect_vec();
+        let mut bodies_sim = self.simulated_bodies();
+
+        // Test particles feel the bodies as they were at the start of the step
+        step_test_particles(
+            self.grav_const,
+            delta as f32,
+            &bodies_sim,
+            &mut self.test_particles,
+        );
+
+        // Execute the maneuvers falling into this step
+        for maneuver in self.take_due_maneuvers(self.sim_time + delta) {
+            maneuver.apply(&mut bodies_sim);
+        }
 
         // Simulate a physics step
-        Self::step_time(self.grav_const, delta as f32, &mut bodies_sim);
+        let generators = self.force_generators();
+        let callable_forces = self.callable_forces();
+        let forces = generators
+            .iter()
+            .map(|f| f.as_ref() as &dyn ForceGenerator)
+            .chain(callable_forces.iter().map(|f| f as &dyn ForceGenerator))
+            .collect_vec();
+
+        Self::step_time(
+            self.grav_const,
+            delta as f32,
+            self.sim_time,
+            &mut bodies_sim,
+            &forces,
+        );
+        self.sim_time += delta;
+
+        apply_tidal_locking(self.tidal_locking, delta as f32, &mut bodies_sim);
 
         // Handle collisions and merging of bodies
         if self.merge_on_collision {
-            let instances_to_remove = Self::merge_bodies(self.merge_scaler, &mut bodies_sim);
+            let merges = Self::merge_bodies(self.merge_scaler, &mut bodies_sim);
 
             // Remove merged bodies from the scene
-            for instance_id in instances_to_remove {
+            for instance_id in merges.into_iter().flat_map(|m| m.absorbed) {
                 if let Some(body) = self
                     .bodies
                     .iter_mut()
@@ -336,6 +1102,18 @@ impl INode3D for GravityController {
             self.get_gravity_bodies();
         }
 
+        // Tear apart bodies that came too close to a heavier body. Collisions are resolved
+        // first, so bodies impacting a heavier body merge instead of being shredded
+        if self.tidal_disruption {
+            let disruptions =
+                Self::disrupt_bodies(self.debris_per_disruption as usize, &mut bodies_sim);
+
+            if !disruptions.is_empty() {
+                self.apply_disruptions(disruptions);
+                self.get_gravity_bodies();
+            }
+        }
+
         if let Some(ov) = self.octree_visualizer.as_mut() {
             // Update the octree visualizer with the simulated bodies
             let octree = MortonBasedOctree::new(&bodies_sim);
@@ -343,6 +1121,16 @@ impl INode3D for GravityController {
             ov.bind_mut().update_visualization(&octree);
         }
 
+        // The hierarchy is only kept current for listeners, queries refresh it themselves
+        if self.hierarchy_due()
+            && !self
+                .base()
+                .get_signal_connection_list("primary_changed")
+                .is_empty()
+        {
+            self.update_hierarchy(&bodies_sim);
+        }
+
         // Apply simulated step to real bodies
         self.bodies
             .iter_mut()
@@ -0,0 +1,1822 @@
+//! Trajectory visualization for the n-body gravity simulation.
+//!
+//! This module provides functionality to simulate and visualize the orbital paths
+//! of celestial bodies in a gravitational system. It extends the `GravityController`
+//! with methods to:
+//!
+//! - Generate predicted orbital trajectories through n-body simulation
+//! - Visualize these trajectories as colored 3D paths
+//! - Manage trajectory display including automatic updates
+//!
+//! Trajectories are represented as sequences of points in 3D space and rendered
+//! using Godot's mesh rendering capabilities. They can be drawn relative to a body,
+//! a pair of bodies or each body's primary, see [`frame`](crate::physics::gravity::frame).
+//!
+//! All trajectories are drawn by a single, reused `ArrayMesh` with vertex colors, so
+//! updating them doesn't add or remove nodes. Each trajectory has its own color, and
+//! can be drawn as lines or camera-facing ribbons. The path after a planned maneuver is
+//! drawn in the maneuver's color.
+//!
+//! Predictions also contain the closest approaches between the `encounter_body` and
+//! other bodies, which are marked by a line between both bodies at the time of the
+//! approach, and the predicted collisions, which are marked where the bodies merge.
+
+use super::{
+    __registration_constants_GravityController, __registration_methods_GravityController,
+    GravityController, SimulatedBody,
+};
+use crate::physics::gravity::{
+    body::GravityBody,
+    decimation::decimate,
+    encounter::{Encounter, find_encounters},
+    field::{self, FieldQuantity},
+    forces::ForceGenerator,
+    frame::{FrameAnchor, FrameTracker, TrajectoryFrame},
+    hierarchy::{OrbitalHierarchy, assign_orbital_velocities, remove_net_momentum},
+    maneuver::PlannedManeuver,
+    rotation::apply_tidal_locking,
+    test_particles::{TestParticle, step_test_particles},
+};
+use crate::{
+    from_glam_vec3,
+    physics::gravity::controller::__gdext_GravityController_Funcs,
+    to_glam_vec3,
+    worker::{CancellationToken, Worker},
+};
+use glam::Vec3A;
+use godot::{
+    classes::{
+        ArrayMesh, Image, ImageTexture3D, MeshInstance3D, Shader, ShaderMaterial,
+        mesh::{ArrayType, PrimitiveType},
+    },
+    prelude::*,
+};
+use itertools::Itertools;
+use std::collections::HashMap;
+
+/// Represents a single body's trajectory path with visual styling information.
+///
+/// A trajectory consists of a collection of 3D points that form the predicted path
+/// of a celestial body over time, along with the colors used for visualization.
+#[derive(Clone)]
+pub struct Trajectory {
+    /// Color of the points added next, changed by maneuvers
+    color: Color,
+    /// Sequential 3D positions forming the predicted path
+    points: Vec<Vec3A>,
+    /// Time of each point since the start of the prediction
+    times: Vec<f32>,
+    /// Color of each point
+    colors: Vec<Color>,
+}
+
+impl Trajectory {
+    fn new(color: Color, start: Vec3A, capacity: usize) -> Self {
+        let mut trajectory = Self {
+            color,
+            points: Vec::with_capacity(capacity),
+            times: Vec::with_capacity(capacity),
+            colors: Vec::with_capacity(capacity),
+        };
+        trajectory.push(start, 0.0);
+
+        trajectory
+    }
+
+    /// Appends a point in the current color.
+    fn push(&mut self, point: Vec3A, time: f32) {
+        self.points.push(point);
+        self.times.push(time);
+        self.colors.push(self.color);
+    }
+
+    /// Returns a copy with at most `budget` points, see [`decimate`].
+    fn decimated(&self, budget: usize, tolerance: f32) -> Self {
+        let kept = decimate(&self.points, budget, tolerance);
+
+        Self {
+            color: self.color,
+            points: kept.iter().map(|&i| self.points[i]).collect(),
+            times: kept.iter().map(|&i| self.times[i]).collect(),
+            colors: kept.iter().map(|&i| self.colors[i]).collect(),
+        }
+    }
+
+    /// Returns the color of the point at or before `time`.
+    fn color_at(&self, time: f32) -> Color {
+        let i = self.times.partition_point(|&t| t <= time);
+        self.colors
+            .get(i.saturating_sub(1))
+            .copied()
+            .unwrap_or(self.color)
+    }
+
+    /// Returns the position at `time` since the start of the prediction, interpolated
+    /// between the points around it.
+    fn position_at(&self, time: f32) -> Option<Vec3A> {
+        let i = self.times.partition_point(|&t| t < time);
+        match (i.checked_sub(1), self.times.get(i)) {
+            (_, Some(&t)) if t == time => Some(self.points[i]),
+            (Some(prev), Some(&t)) => {
+                let progress = (time - self.times[prev]) / (t - self.times[prev]);
+                Some(self.points[prev].lerp(self.points[i], progress))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Upper bound of tick marks along a trajectory. Shorter tick intervals are widened, so
+/// an interval close to 0 doesn't create an unbounded number of ticks.
+const MAX_TICKS: usize = 1000;
+
+/// Draws the trajectory mesh with its vertex colors, expanding ribbons and tick marks
+/// across their path towards the camera that renders them.
+///
+/// Each vertex of a ribbon or tick lies on the path, with the path's direction as its
+/// normal and the signed distance to move it sideways as `UV.x`. Lines and markers have
+/// no offset and are drawn as they are.
+const TRAJECTORY_SHADER: &str = r#"
+shader_type spatial;
+render_mode unshaded, cull_disabled;
+
+void vertex() {
+    if (UV.x != 0.0) {
+        mat4 world_to_model = inverse(MODEL_MATRIX);
+        vec3 view = PROJECTION_MATRIX[3][3] == 1.0
+            ? (world_to_model * vec4(INV_VIEW_MATRIX[2].xyz, 0.0)).xyz
+            : (world_to_model * vec4(INV_VIEW_MATRIX[3].xyz, 1.0)).xyz - VERTEX;
+
+        vec3 side = cross(NORMAL, view);
+        if (dot(side, side) < 1e-12) {
+            side = cross(NORMAL, abs(NORMAL.y) < 0.9 ? vec3(0.0, 1.0, 0.0) : vec3(1.0, 0.0, 0.0));
+        }
+        VERTEX += normalize(side) * UV.x;
+    }
+}
+
+void fragment() {
+    ALBEDO = COLOR.rgb;
+    ALPHA = COLOR.a;
+}
+"#;
+
+/// How trajectories are drawn, from the properties of the [`GravityController`].
+struct TrajectoryStyle {
+    /// Opacity multiplier at the end of the prediction, fading linearly from 1 at its start
+    end_alpha: f32,
+
+    /// Width of camera-facing ribbons, 0 to draw lines
+    ribbon_width: f32,
+
+    /// Prediction time between tick marks, 0 to draw none
+    tick_interval: f32,
+
+    /// Length of the tick marks
+    tick_size: f32,
+
+    /// Size of the crosses at both ends of encounter markers
+    encounter_marker_size: f32,
+
+    encounter_marker_color: Color,
+
+    /// Size of the markers at predicted collisions
+    impact_marker_size: f32,
+
+    impact_marker_color: Color,
+}
+
+/// Contains all necessary information for simulating body trajectories.
+///
+/// This struct encapsulates all the data needed to perform an n-body gravity simulation
+/// for trajectory prediction, including the initial state of bodies, simulation parameters,
+/// and reference frame information.
+pub struct SimulationInfo {
+    /// Current state of all bodies for simulation
+    bodies_sim: Vec<SimulatedBody>,
+
+    /// Trajectory data structures to populate during simulation
+    trajectories: HashMap<InstanceId, Trajectory>,
+
+    /// Current state of the test particles to predict, empty if they aren't predicted
+    test_particles: Vec<TestParticle>,
+
+    /// Trajectories of the test particles, indexed like `test_particles`
+    test_particle_trajectories: Vec<Trajectory>,
+
+    /// Frames the trajectories are drawn in
+    frames: FrameTracker,
+
+    /// Time increment per simulation step in seconds
+    delta: f32,
+
+    /// Simulation time at the first step, used to place kinematic bodies on their paths
+    start_time: f64,
+
+    /// Gravitational constant for force calculations
+    grav_const: f32,
+
+    /// Number of steps to simulate
+    n_steps: usize,
+
+    merge_on_collision: bool,
+
+    merge_scaler: f32,
+
+    tidal_disruption: bool,
+
+    /// Rate of tidal locking, see [`apply_tidal_locking`]
+    tidal_locking: f32,
+
+    /// Non-gravitational forces acting on the bodies
+    forces: Vec<Box<dyn ForceGenerator + Send + Sync>>,
+
+    /// Velocity changes of the bodies, ordered by time
+    maneuvers: Vec<PlannedManeuver>,
+
+    /// Pairs of bodies whose closest approaches are searched
+    encounter_pairs: Vec<(InstanceId, InstanceId)>,
+
+    /// Aborts the simulation once the prediction is stale
+    cancel: CancellationToken,
+
+    /// Identifies the results of this calculation, see `trajectory_generation`
+    generation: u64,
+
+    /// Number of steps between partial results, 0 to only return the finished prediction
+    progress_interval: usize,
+
+    /// Maximum number of points per trajectory, 0 for no limit
+    vertex_budget: usize,
+
+    /// Deviation below which trajectory points are always removed
+    decimation_tolerance: f32,
+}
+
+/// Predicted trajectories and the events found on them.
+#[derive(Default)]
+pub struct Prediction {
+    trajectories: Vec<Trajectory>,
+
+    /// Closest approaches of the `encounter_pairs`, ordered by time
+    encounters: Vec<Encounter>,
+
+    /// Collisions of the bodies, ordered by time
+    merges: Vec<MergeEvent>,
+}
+
+/// A predicted collision of bodies that merge into one.
+#[derive(Clone, Debug)]
+pub struct MergeEvent {
+    /// Simulation time of the merge
+    pub time: f64,
+
+    /// Position of the merged body, in the frame of the survivor's trajectory
+    pub position: Vec3A,
+
+    /// The body that absorbs the others
+    pub survivor: InstanceId,
+
+    /// The bodies that are absorbed, whose trajectories end at `position`
+    pub absorbed: Vec<InstanceId>,
+}
+
+/// Prediction sent back by the worker thread.
+pub struct TrajectoryResult {
+    prediction: Prediction,
+
+    /// Whether all steps were simulated, or this is a partial result of a running prediction
+    complete: bool,
+
+    /// Generation of the calculation, results of older calculations are dropped
+    generation: u64,
+}
+
+/// Manages a background thread for trajectory calculations.
+///
+/// This worker handles trajectory simulations asynchronously, allowing the main game thread
+/// to continue running smoothly while calculations are performed in the background.
+pub type TrajectoryWorker = Worker<TrajectoryResult, TrajectoryCommand>;
+
+/// Commands that can be sent to the trajectory worker thread.
+///
+/// This enum defines the communication protocol between the main thread and
+/// the trajectory calculation thread.
+pub enum TrajectoryCommand {
+    /// Request to calculate trajectories with the provided simulation information
+    Calculate(Box<SimulationInfo>),
+    /// Signal the worker thread to terminate
+    Shutdown,
+}
+
+#[godot_api(secondary)]
+impl GravityController {
+    /// Enables trajectory visualization and starts the worker thread.
+    ///
+    /// This function initializes a background thread for trajectory calculation if not already running.
+    /// It sets up communication channels and queues an initial trajectory calculation.
+    #[func]
+    fn enable_trajectories(&mut self) {
+        // If the worker is already running, do nothing
+        if self.trajectory_worker.is_some() {
+            return;
+        }
+
+        self.trajectory_worker = Some(Self::spawn_trajectory_worker());
+
+        // Queue the first trajectory calculation
+        self.queue_simulate_trajectories();
+    }
+
+    /// Disables trajectory visualization and shuts down the worker thread.
+    ///
+    /// This function gracefully terminates the background trajectory calculation thread
+    /// and removes any existing trajectory visualizations from the scene.
+    #[func]
+    fn disable_trajectories(&mut self) {
+        if let Some(cancel) = self.trajectory_cancel.take() {
+            cancel.cancel();
+        }
+
+        if let Some(worker) = self.trajectory_worker.take() {
+            worker
+                .send_command(TrajectoryCommand::Shutdown)
+                .unwrap_or_else(|e| godot_error!("Failed to send shutdown command: {}", e));
+
+            worker.join().unwrap_or_else(|_| {
+                godot_error!("Failed to join trajectory worker thread on shutdown")
+            });
+        }
+
+        self.clear_trajectories();
+    }
+
+    /// Queues a new trajectory calculation request to the worker thread.
+    ///
+    /// This function collects the current simulation parameters and body states,
+    /// and sends them to the worker thread for asynchronous trajectory calculation.
+    /// A calculation that is still running is stale from now on and gets cancelled.
+    #[func]
+    fn queue_simulate_trajectories(&mut self) {
+        if self.trajectory_worker.is_none() {
+            return;
+        }
+
+        if let Some(cancel) = self.trajectory_cancel.take() {
+            cancel.cancel();
+        }
+
+        self.trajectory_generation += 1;
+        let mut info = self.get_simulation_info();
+        info.progress_interval = self.trajectory_progress_interval as usize;
+        self.trajectory_cancel = Some(info.cancel.clone());
+
+        if let Some(worker) = &self.trajectory_worker {
+            let _ = worker.send_command(TrajectoryCommand::Calculate(Box::new(info)));
+        }
+    }
+
+    /// Whether a queued trajectory calculation hasn't finished yet.
+    ///
+    /// Queue the next calculation only once this returns false, so that predictions taking
+    /// longer than a frame aren't cancelled before they finish.
+    #[func]
+    fn is_predicting_trajectories(&self) -> bool {
+        self.trajectory_cancel.is_some()
+    }
+
+    /// Polls for and applies any newly calculated trajectories.
+    ///
+    /// This function should be called periodically from the main thread to check if
+    /// the worker thread has completed any trajectory calculations. If new trajectories
+    /// are available, they are used to update the visualization.
+    ///
+    /// Partial results of a running calculation are only shown while no finished
+    /// prediction is displayed, so long predictions draw progressively without the
+    /// displayed paths shrinking whenever a new calculation starts. Results of
+    /// calculations that were queued over are dropped.
+    ///
+    /// If the worker thread died, it's restarted and the running calculation is dropped,
+    /// so the next one can be queued.
+    #[func]
+    fn poll_trajectory_results(&mut self) {
+        let Some(worker) = self.trajectory_worker.as_ref() else {
+            return;
+        };
+        let stopped = worker.is_finished();
+
+        let (latest_complete, latest_partial) =
+            Self::latest_predictions(worker.try_recv_all(), self.trajectory_generation);
+
+        if let Some(prediction) = latest_complete {
+            self.trajectory_cancel = None;
+            self.replace_prediction(prediction);
+            self.trajectories_complete = true;
+        }
+
+        if let Some(prediction) = latest_partial
+            && !self.trajectories_complete
+        {
+            self.replace_prediction(prediction);
+        }
+
+        if stopped {
+            godot_error!("Trajectory worker thread stopped unexpectedly, restarting it");
+            self.trajectory_cancel = None;
+            if let Some(worker) = self.trajectory_worker.as_mut() {
+                Self::respawn_trajectory_worker(worker);
+            }
+        }
+    }
+
+    /// Simulates and visualizes orbital trajectories for all registered celestial bodies.
+    ///
+    /// This function performs an n-body gravity simulation for the specified number of steps,
+    /// generating trajectory data for each body in the system. The simulation:
+    ///
+    /// - Uses the configured gravity constant, number of steps, and time delta
+    /// - Calculates the gravitational interactions between all bodies
+    /// - Draws the trajectories in the frame selected by `trajectory_frame`
+    /// - Stores position data at each step to create visual trajectory paths
+    /// - Updates the scene with the newly calculated trajectories
+    ///
+    /// After simulation, the trajectories are visualized as colored paths showing the predicted
+    /// orbital movement of each body.
+    ///
+    /// A calculation queued on the worker before is cancelled, as it's stale now.
+    #[func]
+    fn simulate_trajectories(&mut self) {
+        if let Some(cancel) = self.trajectory_cancel.take() {
+            cancel.cancel();
+        }
+
+        self.trajectory_generation += 1;
+        let info = self.get_simulation_info();
+        if let Some(prediction) = Self::simulate_trajectories_inner(info, |_| {}) {
+            self.replace_prediction(prediction);
+            self.trajectories_complete = true;
+        }
+    }
+
+    /// Removes all trajectories currently displayed in the scene.
+    ///
+    /// This function clears the trajectory visualization without affecting other simulation settings.
+    /// It does this by replacing the current prediction with an empty one.
+    #[func]
+    fn clear_trajectories(&mut self) {
+        self.replace_prediction(Prediction::default());
+        self.trajectories_complete = false;
+    }
+
+    /// Updates trajectory visualizations if auto_update_trajectories is enabled
+    #[func]
+    fn update_trajectories(&mut self) {
+        if self.auto_update_trajectories {
+            self.simulate_trajectories();
+        }
+    }
+
+    /// Returns the closest approaches found by the displayed prediction, ordered by time.
+    ///
+    /// Each is a dictionary with the `body` and `target`, the simulation `time` and
+    /// `distance` of the approach, and the `body_position` and `target_position` in the
+    /// space the trajectories are drawn in. Encounters are searched between
+    /// `encounter_body` and `encounter_target`, or all other bodies without a target.
+    #[func]
+    fn get_encounters(&self) -> Array<Dictionary> {
+        let find = |id: InstanceId| self.bodies.iter().find(|b| b.instance_id() == id).cloned();
+
+        self.encounters
+            .iter()
+            .map(|e| {
+                dict! {
+                    "body": find(e.body),
+                    "target": find(e.target),
+                    "time": e.time,
+                    "distance": e.distance,
+                    "body_position": from_glam_vec3(e.body_position),
+                    "target_position": from_glam_vec3(e.target_position),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the collisions found by the displayed prediction, ordered by time.
+    ///
+    /// Each is a dictionary with the simulation `time` of the merge, its `position` in the
+    /// space the trajectories are drawn in, the `survivor` and the `absorbed` bodies.
+    /// Collisions are only predicted with `merge_on_collision`.
+    #[func]
+    fn get_predicted_merges(&self) -> Array<Dictionary> {
+        let find = |id: &InstanceId| self.bodies.iter().find(|b| b.instance_id() == *id).cloned();
+
+        self.predicted_merges
+            .iter()
+            .map(|m| {
+                dict! {
+                    "time": m.time,
+                    "position": from_glam_vec3(m.position),
+                    "survivor": find(&m.survivor),
+                    "absorbed": m.absorbed.iter().filter_map(find).collect::<Array<_>>(),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the time in seconds until `body` is predicted to collide with another body,
+    /// or -1 if no collision is predicted.
+    #[func]
+    fn get_time_until_merge(&self, body: Gd<GravityBody>) -> f64 {
+        let id = body.instance_id();
+
+        self.predicted_merges
+            .iter()
+            .find(|m| m.survivor == id || m.absorbed.contains(&id))
+            .map_or(-1.0, |m| (m.time - self.sim_time).max(0.0))
+    }
+
+    /// Gives every body the velocity of an orbit around its dominant attractor.
+    ///
+    /// The attractor is the body with the smallest Hill sphere containing the body, so
+    /// moons orbit their planets. Bodies are put on circular orbits, or on the periapsis of
+    /// an orbit with `orbit_eccentricity`. Pinned and kinematic bodies are left unchanged.
+    #[func]
+    fn assign_orbital_velocities(&mut self) {
+        let mut bodies_sim = self.bodies.iter().map(SimulatedBody::from).collect_vec();
+
+        assign_orbital_velocities(self.grav_const, self.orbit_eccentricity, &mut bodies_sim);
+        if self.zero_net_momentum {
+            remove_net_momentum(&mut bodies_sim);
+        }
+
+        for (body, sim) in self.bodies.iter_mut().zip(&bodies_sim) {
+            if sim.motion.has_free_velocity() {
+                body.bind_mut().velocity = from_glam_vec3(sim.vel);
+            }
+        }
+
+        self.update_trajectories();
+    }
+
+    /// Returns who orbits whom.
+    ///
+    /// Maps every body to a dictionary with its `parent` (null for the roots), its
+    /// `children`, its `depth` below the roots, its `hill_radius` and
+    /// `sphere_of_influence` radius, and whether it is `bound` to its primary.
+    #[func]
+    fn get_orbital_hierarchy(&mut self) -> Dictionary {
+        self.refresh_hierarchy();
+
+        self.hierarchy
+            .nodes
+            .iter()
+            .filter_map(|node| Some((node, self.body_by_id(node.body)?)))
+            .map(|(node, body)| {
+                let children = node
+                    .children
+                    .iter()
+                    .filter_map(|&c| self.body_by_id(self.hierarchy.nodes[c].body))
+                    .collect::<Array<_>>();
+
+                let info = dict! {
+                    "parent": node.parent_body.and_then(|id| self.body_by_id(id)),
+                    "children": children,
+                    "depth": node.depth as i64,
+                    "hill_radius": node.hill_radius,
+                    "sphere_of_influence": node.sphere_of_influence,
+                    "bound": node.bound,
+                };
+
+                (body, info)
+            })
+            .collect()
+    }
+
+    /// Returns the body that `body` orbits, or null if it orbits nothing.
+    #[func]
+    fn get_primary(&mut self, body: Gd<GravityBody>) -> Option<Gd<GravityBody>> {
+        self.refresh_hierarchy();
+
+        let id = body.instance_id();
+        let node = self.hierarchy.nodes.iter().find(|n| n.body == id)?;
+        node.parent_body
+            .and_then(|primary| self.body_by_id(primary))
+    }
+
+    /// Returns the gravitational acceleration of all bodies at `position`.
+    ///
+    /// The point has no mass, so it feels the bodies like a test particle does. Useful for
+    /// ships and other Godot physics bodies, see [`GravityReceiver`].
+    ///
+    /// [`GravityReceiver`]: crate::physics::gravity::receiver::GravityReceiver
+    #[func]
+    fn sample_acceleration(&self, position: Vector3) -> Vector3 {
+        self.sample_accelerations_at(&[to_glam_vec3(position)])
+            .first()
+            .copied()
+            .map(from_glam_vec3)
+            .unwrap_or_default()
+    }
+
+    /// Returns the gravitational acceleration of all bodies at each of `positions`.
+    ///
+    /// Cheaper than calling `sample_acceleration` for every point, as the bodies are only
+    /// collected once and large batches are evaluated in parallel.
+    #[func]
+    fn sample_accelerations(&self, positions: PackedVector3Array) -> PackedVector3Array {
+        let points = positions
+            .as_slice()
+            .iter()
+            .copied()
+            .map(to_glam_vec3)
+            .collect_vec();
+
+        self.sample_accelerations_at(&points)
+            .into_iter()
+            .map(from_glam_vec3)
+            .collect()
+    }
+
+    /// Samples the gravity field on the parallelogram spanned by `axis_u` and `axis_v`
+    /// from `origin` into a `FORMAT_RF` image of the given resolution.
+    ///
+    /// Returns null for an empty resolution.
+    #[func]
+    fn sample_field_slice(
+        &self,
+        quantity: FieldQuantity,
+        origin: Vector3,
+        axis_u: Vector3,
+        axis_v: Vector3,
+        resolution: Vector2i,
+    ) -> Option<Gd<Image>> {
+        field::sample_slice(
+            self.grav_const,
+            &self.simulated_bodies(),
+            quantity,
+            origin,
+            axis_u,
+            axis_v,
+            resolution,
+        )
+    }
+
+    /// Samples the gravity field on a regular grid filling `bounds` into a `FORMAT_RF`
+    /// 3D texture of the given resolution.
+    ///
+    /// Returns null for an empty resolution.
+    #[func]
+    fn sample_field_volume(
+        &self,
+        quantity: FieldQuantity,
+        bounds: Aabb,
+        resolution: Vector3i,
+    ) -> Option<Gd<ImageTexture3D>> {
+        field::sample_volume(
+            self.grav_const,
+            &self.simulated_bodies(),
+            quantity,
+            bounds,
+            resolution,
+        )
+    }
+
+    /// Returns the Lagrange points L1 to L5 of `secondary` orbiting `primary`.
+    ///
+    /// The points come from the restricted three-body problem. With `refine`, they are
+    /// moved onto the equilibria of the gravity field of all bodies. Returns an empty
+    /// array if the points are undefined.
+    #[func]
+    fn get_lagrange_points(
+        &self,
+        primary: Gd<GravityBody>,
+        secondary: Gd<GravityBody>,
+        refine: bool,
+    ) -> PackedVector3Array {
+        self.lagrange_points(&primary, &secondary, refine)
+            .map(|points| points.into_iter().map(from_glam_vec3).collect())
+            .unwrap_or_default()
+    }
+
+    /// Adds massless test particles with the given positions and velocities.
+    ///
+    /// Test particles are attracted by the bodies but don't attract anything themselves,
+    /// which makes them cheap enough for asteroid belts, rings and debris. Missing
+    /// velocities default to zero.
+    #[func]
+    fn add_test_particles(
+        &mut self,
+        positions: PackedVector3Array,
+        velocities: PackedVector3Array,
+    ) {
+        if velocities.len() > positions.len() {
+            godot_warn!(
+                "Got {} velocities for {} test particles, ignoring the rest",
+                velocities.len(),
+                positions.len()
+            );
+        }
+
+        let velocities = velocities.as_slice().iter().copied();
+        self.test_particles.extend(
+            positions
+                .as_slice()
+                .iter()
+                .zip(velocities.chain(std::iter::repeat(Vector3::ZERO)))
+                .map(|(&pos, vel)| TestParticle {
+                    pos: to_glam_vec3(pos),
+                    vel: to_glam_vec3(vel),
+                }),
+        );
+    }
+
+    /// Removes the test particles at the given indices.
+    ///
+    /// The remaining particles keep their order, so their indices shift down.
+    #[func]
+    fn remove_test_particles(&mut self, indices: PackedInt32Array) {
+        let mut remove = vec![false; self.test_particles.len()];
+        for &i in indices.as_slice() {
+            match remove.get_mut(i as usize) {
+                Some(r) if i >= 0 => *r = true,
+                _ => godot_error!("Test particle index out of bounds: {}", i),
+            }
+        }
+
+        let mut remove = remove.into_iter();
+        self.test_particles
+            .retain(|_| !remove.next().unwrap_or(false));
+    }
+
+    /// Removes all test particles.
+    #[func]
+    fn clear_test_particles(&mut self) {
+        self.test_particles.clear();
+    }
+
+    /// Returns the number of test particles.
+    #[func]
+    fn get_test_particle_count(&self) -> i64 {
+        self.test_particles.len() as i64
+    }
+
+    /// Returns the positions of all test particles, in the order they were added.
+    #[func]
+    fn get_test_particle_positions(&self) -> PackedVector3Array {
+        self.test_particles
+            .iter()
+            .map(|p| from_glam_vec3(p.pos))
+            .collect()
+    }
+
+    /// Returns the velocities of all test particles, in the order they were added.
+    #[func]
+    fn get_test_particle_velocities(&self) -> PackedVector3Array {
+        self.test_particles
+            .iter()
+            .map(|p| from_glam_vec3(p.vel))
+            .collect()
+    }
+}
+
+impl GravityController {
+    /// Starts a background thread calculating the queued trajectory predictions.
+    fn spawn_trajectory_worker() -> TrajectoryWorker {
+        TrajectoryWorker::new(|cmd_receiver, result_tx| {
+            while let Ok(batch) = cmd_receiver.recv_batch() {
+                match batch.find_or_latest(|c| matches!(c, TrajectoryCommand::Shutdown)) {
+                    TrajectoryCommand::Shutdown => break,
+
+                    TrajectoryCommand::Calculate(info) => {
+                        let generation = info.generation;
+                        let send = |prediction, complete| {
+                            let result = TrajectoryResult {
+                                prediction,
+                                complete,
+                                generation,
+                            };
+
+                            if let Err(e) = result_tx.send(result) {
+                                godot_error!("Failed to send trajectory results: {}", e);
+                            }
+                        };
+
+                        // Cancelled predictions send nothing more
+                        if let Some(prediction) =
+                            Self::simulate_trajectories_inner(*info, |partial| send(partial, false))
+                        {
+                            send(prediction, true);
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    /// Picks the newest finished and partial prediction of `generation` from the results of
+    /// the worker, which are ordered oldest first.
+    ///
+    /// Results of other generations are dropped, and so are partial results that arrived
+    /// before the finished prediction.
+    fn latest_predictions(
+        results: impl IntoIterator<Item = TrajectoryResult>,
+        generation: u64,
+    ) -> (Option<Prediction>, Option<Prediction>) {
+        let mut latest_complete = None;
+        let mut latest_partial = None;
+
+        for result in results.into_iter().filter(|r| r.generation == generation) {
+            if result.complete {
+                latest_complete = Some(result.prediction);
+                latest_partial = None;
+            } else {
+                latest_partial = Some(result.prediction);
+            }
+        }
+
+        (latest_complete, latest_partial)
+    }
+
+    /// Replaces a worker whose thread stopped with a new one.
+    fn respawn_trajectory_worker(worker: &mut TrajectoryWorker) {
+        let stopped = std::mem::replace(worker, Self::spawn_trajectory_worker());
+        let _ = stopped.join();
+    }
+
+    /// Brings the hierarchy up to date with the bodies.
+    ///
+    /// While physics steps run, the hierarchy is reused until `hierarchy_update_interval`
+    /// has passed. It's rebuilt in the editor, while physics processing is disabled, when
+    /// it's due, or when the bodies changed since the last update.
+    pub(super) fn refresh_hierarchy(&mut self) {
+        let current = self.base().is_physics_processing()
+            && !self.hierarchy_due()
+            && self
+                .hierarchy
+                .nodes
+                .iter()
+                .map(|n| n.body)
+                .eq(self.bodies.iter().map(|b| b.instance_id()));
+
+        if !current {
+            let bodies_sim = self.simulated_bodies();
+            self.update_hierarchy(&bodies_sim);
+        }
+    }
+
+    /// Collects simulation parameters and prepares data for trajectory calculation.
+    ///
+    /// This function gathers the current state of all bodies, creates empty trajectory
+    /// structures, and determines the reference frame for relative trajectories if needed.
+    ///
+    /// # Returns
+    ///
+    /// A `SimulationInfo` struct containing all data needed for trajectory simulation.
+    fn get_simulation_info(&self) -> SimulationInfo {
+        let n_steps = self.simulation_steps as usize;
+        let delta = self.simulation_step_delta;
+        let grav_const = self.grav_const;
+
+        let (bodies_sim, trajectories): (Vec<_>, HashMap<_, _>) = self
+            .simulated_bodies()
+            .into_iter()
+            .zip(self.bodies.iter().map(|b| b.bind().trajectory_color))
+            .map(|(b, color)| {
+                let trajectory = Trajectory::new(color, b.pos, n_steps);
+                let instance_id = b.body_instance_id;
+
+                (b, (instance_id, trajectory))
+            })
+            .unzip();
+
+        let test_particles = if self.predict_test_particles {
+            self.test_particles.clone()
+        } else {
+            Vec::new()
+        };
+
+        let test_particle_trajectories = test_particles
+            .iter()
+            .map(|p| Trajectory::new(self.test_particle_trajectory_color, p.pos, n_steps))
+            .collect();
+
+        let encounter_pairs = match &self.encounter_body {
+            Some(body) => {
+                let id = body.instance_id();
+                match &self.encounter_target {
+                    Some(target) => vec![(id, target.instance_id())],
+                    None => bodies_sim
+                        .iter()
+                        .map(|b| (id, b.body_instance_id))
+                        .filter(|&(id, other)| id != other)
+                        .collect(),
+                }
+            }
+            None => Vec::new(),
+        };
+
+        // Built here rather than reused, as physics steps that keep the controller's hierarchy
+        // up to date don't run in the editor
+        let hierarchy = OrbitalHierarchy::new(grav_const, self.hierarchy_sphere, &bodies_sim);
+
+        let frames = {
+            let anchor = FrameAnchor::new(
+                self.trajectory_frame,
+                self.sim_center_body.as_ref().map(Gd::instance_id),
+                self.frame_secondary_body.as_ref().map(Gd::instance_id),
+            );
+
+            // Each body relative to its primary
+            let anchors = match self.trajectory_frame {
+                TrajectoryFrame::Auto => hierarchy
+                    .nodes
+                    .iter()
+                    .filter_map(|node| Some((node.body, FrameAnchor::Body(node.parent_body?))))
+                    .collect(),
+                _ => HashMap::new(),
+            };
+
+            FrameTracker::new(&bodies_sim, anchor, anchors)
+        };
+
+        // Callables can't be called from the worker thread, so their force is held constant
+        let mut forces = self.force_generators();
+        forces.extend(
+            self.callable_forces()
+                .iter()
+                .map(|f| Box::new(f.sample(&bodies_sim)) as Box<dyn ForceGenerator + Send + Sync>),
+        );
+
+        SimulationInfo {
+            bodies_sim,
+            trajectories,
+            test_particles,
+            test_particle_trajectories,
+            frames,
+            delta,
+            start_time: self.sim_time,
+            grav_const,
+            n_steps,
+            merge_on_collision: self.merge_on_collision,
+            merge_scaler: self.merge_scaler,
+            tidal_disruption: self.tidal_disruption,
+            tidal_locking: self.tidal_locking,
+            forces,
+            maneuvers: self.planned_maneuvers(&hierarchy),
+            encounter_pairs,
+            cancel: CancellationToken::new(),
+            generation: self.trajectory_generation,
+            progress_interval: 0,
+            vertex_budget: self.trajectory_vertex_budget as usize,
+            decimation_tolerance: self.trajectory_decimation_tolerance,
+        }
+    }
+
+    /// Performs the n-body gravity simulation to generate trajectory data.
+    ///
+    /// This function runs the actual physics simulation, updating positions and velocities
+    /// for all bodies over multiple time steps and recording the resulting trajectories.
+    ///
+    /// # Parameters
+    ///
+    /// * `SimulationInfo` - Contains all simulation parameters and bodies' initial states
+    /// * `progress` - Called with the prediction so far every `progress_interval` steps
+    ///
+    /// # Returns
+    ///
+    /// A `Prediction` containing the simulated orbital paths, including the paths of the
+    /// test particles if they are predicted, and the encounters between the bodies.
+    /// `None` if the simulation was cancelled.
+    fn simulate_trajectories_inner(
+        SimulationInfo {
+            mut bodies_sim,
+            mut trajectories,
+            mut test_particles,
+            mut test_particle_trajectories,
+            mut frames,
+            delta,
+            start_time,
+            grav_const,
+            n_steps,
+            merge_on_collision,
+            merge_scaler,
+            tidal_disruption,
+            tidal_locking,
+            forces,
+            maneuvers,
+            encounter_pairs,
+            cancel,
+            generation: _,
+            progress_interval,
+            vertex_budget,
+            decimation_tolerance,
+        }: SimulationInfo,
+        mut progress: impl FnMut(Prediction),
+    ) -> Option<Prediction> {
+        let forces = forces
+            .iter()
+            .map(|f| f.as_ref() as &dyn ForceGenerator)
+            .collect_vec();
+
+        let mut next_maneuver = 0;
+        let mut merges = Vec::new();
+
+        // Separations are measured in the controller's space, as the trajectories of a pair
+        // may be drawn in different frames
+        let mut separations = encounter_pairs
+            .iter()
+            .map(|&pair| (pair, Vec::with_capacity(n_steps)))
+            .collect_vec();
+        Self::record_separations(&bodies_sim, &mut separations);
+
+        for step in 1..n_steps {
+            if cancel.is_cancelled() {
+                return None;
+            }
+
+            if progress_interval > 0 && step % progress_interval == 0 {
+                progress(Self::prediction(
+                    &trajectories,
+                    &test_particle_trajectories,
+                    &separations,
+                    &merges,
+                    start_time,
+                    vertex_budget,
+                    decimation_tolerance,
+                ));
+            }
+
+            // Apply the maneuvers falling into this step, the paths change color after them
+            let time = start_time + (step - 1) as f64 * f64::from(delta);
+            let due = maneuvers.partition_point(|m| m.maneuver.time < time + f64::from(delta));
+            for planned in &maneuvers[next_maneuver..due] {
+                if planned.apply(&mut bodies_sim)
+                    && let Some(trajectory) = trajectories.get_mut(&planned.body)
+                {
+                    trajectory.color = planned.maneuver.color;
+                }
+            }
+            next_maneuver = due;
+
+            // Step
+            step_test_particles(grav_const, delta, &bodies_sim, &mut test_particles);
+            Self::step_time(grav_const, delta, time, &mut bodies_sim, &forces);
+            apply_tidal_locking(tidal_locking, delta, &mut bodies_sim);
+
+            // Check for collisions
+            let step_merges = if merge_on_collision {
+                Self::merge_bodies(merge_scaler, &mut bodies_sim)
+            } else {
+                Vec::new()
+            };
+
+            // Disrupted bodies become debris, which is not predicted
+            if tidal_disruption {
+                let _ = Self::disrupt_bodies(0, &mut bodies_sim);
+            }
+
+            frames.update(&bodies_sim);
+            Self::record_separations(&bodies_sim, &mut separations);
+            let elapsed = step as f32 * delta;
+
+            // Absorbed bodies' trajectories end where they merge
+            for merge in step_merges {
+                for absorbed in &merge.absorbed {
+                    if let Some(trajectory) = trajectories.get_mut(absorbed) {
+                        trajectory.push(frames.body_point(*absorbed, merge.pos), elapsed);
+                    }
+                }
+
+                merges.push(MergeEvent {
+                    time: start_time + f64::from(elapsed),
+                    position: frames.body_point(merge.survivor, merge.pos),
+                    survivor: merge.survivor,
+                    absorbed: merge.absorbed,
+                });
+            }
+
+            // Store positions
+            for body in bodies_sim.iter() {
+                let instance_id = body.body_instance_id;
+                let trajectory = trajectories
+                    .get_mut(&instance_id)
+                    .expect("Trajectory not found for body");
+
+                // Append the new position to the trajectory
+                trajectory.push(frames.body_point(instance_id, body.pos), elapsed);
+            }
+
+            for (particle, trajectory) in test_particles.iter().zip(&mut test_particle_trajectories)
+            {
+                trajectory.push(frames.point(particle.pos), elapsed);
+            }
+        }
+
+        Some(Self::prediction(
+            &trajectories,
+            &test_particle_trajectories,
+            &separations,
+            &merges,
+            start_time,
+            vertex_budget,
+            decimation_tolerance,
+        ))
+    }
+
+    /// Appends the current separation of each pair whose bodies both still exist.
+    fn record_separations(
+        bodies_sim: &[SimulatedBody],
+        separations: &mut [((InstanceId, InstanceId), Vec<f32>)],
+    ) {
+        if separations.is_empty() {
+            return;
+        }
+
+        let positions = bodies_sim
+            .iter()
+            .map(|b| (b.body_instance_id, b.pos))
+            .collect::<HashMap<_, _>>();
+
+        for ((body, target), distances) in separations {
+            if let (Some(a), Some(b)) = (positions.get(body), positions.get(target)) {
+                distances.push(a.distance(*b));
+            }
+        }
+    }
+
+    /// Searches the encounters on the full-resolution trajectories, then decimates them.
+    fn prediction(
+        trajectories: &HashMap<InstanceId, Trajectory>,
+        test_particle_trajectories: &[Trajectory],
+        separations: &[((InstanceId, InstanceId), Vec<f32>)],
+        merges: &[MergeEvent],
+        start_time: f64,
+        vertex_budget: usize,
+        decimation_tolerance: f32,
+    ) -> Prediction {
+        let encounters = separations
+            .iter()
+            .filter_map(|&((body, target), ref distances)| {
+                let (a, b) = (trajectories.get(&body)?, trajectories.get(&target)?);
+                Some(find_encounters(
+                    body, target, start_time, &a.times, distances, &a.points, &b.points,
+                ))
+            })
+            .flatten()
+            .sorted_by(|a, b| a.time.total_cmp(&b.time))
+            .collect();
+
+        Prediction {
+            trajectories: trajectories
+                .values()
+                .chain(test_particle_trajectories)
+                .map(|t| t.decimated(vertex_budget, decimation_tolerance))
+                .collect(),
+            encounters,
+            merges: merges.to_vec(),
+        }
+    }
+
+    /// Replaces the displayed prediction with a new one.
+    ///
+    /// All trajectories and markers are written into a single `ArrayMesh` with vertex
+    /// colors, which is reused across updates. The mesh instance displaying it is created
+    /// on the first update with something to draw, so updating never adds or removes nodes
+    /// afterwards.
+    fn replace_prediction(&mut self, prediction: Prediction) {
+        let style = self.trajectory_style();
+        let geometry = TrajectoryGeometry::new(&prediction, &style);
+        self.encounters = prediction.encounters;
+        self.predicted_merges = prediction.merges;
+
+        if geometry.is_empty() && self.trajectory_instance.is_none() {
+            return;
+        }
+
+        let mut mesh = self.trajectory_mesh();
+        mesh.clear_surfaces();
+
+        // Ribbons and ticks are expanded by the shader, beyond the bounds of the mesh
+        if let Some(instance) = self.trajectory_instance.as_mut() {
+            instance.set_extra_cull_margin(style.ribbon_width.max(style.tick_size).max(0.0) / 2.0);
+        }
+
+        let primitive = if style.ribbon_width > 0.0 {
+            PrimitiveType::TRIANGLES
+        } else {
+            PrimitiveType::LINES
+        };
+        for (primitive, surface) in [
+            (primitive, geometry.paths),
+            (PrimitiveType::LINES, geometry.ticks),
+            (PrimitiveType::LINES, geometry.markers),
+        ] {
+            if !surface.vertices.is_empty() {
+                mesh.add_surface_from_arrays(primitive, &surface.to_arrays());
+            }
+        }
+    }
+
+    /// Returns the mesh displaying the trajectories, creating its mesh instance on first use.
+    fn trajectory_mesh(&mut self) -> Gd<ArrayMesh> {
+        if let Some(mesh) = self
+            .trajectory_instance
+            .as_ref()
+            .and_then(|instance| instance.get_mesh())
+            .and_then(|mesh| mesh.try_cast::<ArrayMesh>().ok())
+        {
+            return mesh;
+        }
+
+        // Material using the vertex colors, shared by all trajectories
+        let mut shader = Shader::new_gd();
+        shader.set_code(TRAJECTORY_SHADER);
+        let mut material = ShaderMaterial::new_gd();
+        material.set_shader(&shader);
+
+        let mesh = ArrayMesh::new_gd();
+        let mut instance = MeshInstance3D::new_alloc();
+        instance.set_mesh(&mesh);
+        instance.set_material_override(&material);
+
+        self.base_mut().add_child(&instance);
+        self.trajectory_instance = Some(instance);
+
+        mesh
+    }
+
+    /// Collects the trajectory drawing properties.
+    fn trajectory_style(&self) -> TrajectoryStyle {
+        TrajectoryStyle {
+            end_alpha: self.trajectory_end_alpha,
+            ribbon_width: self.trajectory_ribbon_width,
+            tick_interval: self.trajectory_tick_interval,
+            tick_size: self.trajectory_tick_size,
+            encounter_marker_size: self.encounter_marker_size,
+            encounter_marker_color: self.encounter_marker_color,
+            impact_marker_size: self.impact_marker_size,
+            impact_marker_color: self.impact_marker_color,
+        }
+    }
+}
+
+/// Vertices of one surface of the trajectory mesh, see [`TRAJECTORY_SHADER`].
+#[derive(Default)]
+struct SurfaceArrays {
+    /// Positions on the center line of ribbons and ticks
+    vertices: Vec<Vec3A>,
+
+    /// Direction of the path, which ribbons and ticks are expanded across
+    tangents: Vec<Vec3A>,
+
+    /// Signed distance to expand each vertex across the path
+    offsets: Vec<f32>,
+
+    colors: Vec<Color>,
+}
+
+impl SurfaceArrays {
+    fn push(&mut self, vertex: Vec3A, tangent: Vec3A, offset: f32, color: Color) {
+        self.vertices.push(vertex);
+        self.tangents.push(tangent);
+        self.offsets.push(offset);
+        self.colors.push(color);
+    }
+
+    /// Converts the vertices to the arrays of a mesh surface, with the tangents as normals
+    /// and the offsets as `UV.x`.
+    fn to_arrays(&self) -> VariantArray {
+        let vertices = self
+            .vertices
+            .iter()
+            .map(|&v| from_glam_vec3(v))
+            .collect::<PackedVector3Array>();
+        let tangents = self
+            .tangents
+            .iter()
+            .map(|&t| from_glam_vec3(t))
+            .collect::<PackedVector3Array>();
+        let offsets = self
+            .offsets
+            .iter()
+            .map(|&offset| Vector2::new(offset, 0.0))
+            .collect::<PackedVector2Array>();
+        let colors = self.colors.iter().copied().collect::<PackedColorArray>();
+
+        let mut arrays = VariantArray::new();
+        arrays.resize(ArrayType::MAX.ord() as usize, &Variant::nil());
+        arrays.set(ArrayType::VERTEX.ord() as usize, &vertices.to_variant());
+        arrays.set(ArrayType::NORMAL.ord() as usize, &tangents.to_variant());
+        arrays.set(ArrayType::TEX_UV.ord() as usize, &offsets.to_variant());
+        arrays.set(ArrayType::COLOR.ord() as usize, &colors.to_variant());
+        arrays
+    }
+}
+
+/// Vertices of all displayed trajectories, before they are uploaded to the mesh.
+#[derive(Default)]
+struct TrajectoryGeometry {
+    /// Line segments, or ribbon triangles, of the paths
+    paths: SurfaceArrays,
+
+    /// Line segments of the tick marks
+    ticks: SurfaceArrays,
+
+    /// Line segments of the encounter and impact markers
+    markers: SurfaceArrays,
+}
+
+impl TrajectoryGeometry {
+    /// Builds the geometry of a prediction. Trajectories with less than 2 points are skipped.
+    fn new(prediction: &Prediction, style: &TrajectoryStyle) -> Self {
+        let mut geometry = Self::default();
+
+        for trajectory in prediction
+            .trajectories
+            .iter()
+            .filter(|traj| traj.points.len() >= 2)
+        {
+            geometry.append(trajectory, style);
+        }
+        for encounter in &prediction.encounters {
+            geometry.append_encounter(encounter, style);
+        }
+        for merge in &prediction.merges {
+            geometry.append_impact(merge, style);
+        }
+
+        geometry
+    }
+
+    /// Whether there is nothing to draw.
+    fn is_empty(&self) -> bool {
+        [&self.paths, &self.ticks, &self.markers]
+            .iter()
+            .all(|surface| surface.vertices.is_empty())
+    }
+
+    /// Appends the geometry of a trajectory.
+    ///
+    /// The path is drawn as line segments, or as a camera-facing ribbon when the style has
+    /// a ribbon width. Vertex colors fade from the points' colors at the start of the
+    /// prediction to `end_alpha` times their opacity at the end. Tick marks are short
+    /// camera-facing lines across the path, at most [`MAX_TICKS`] per trajectory.
+    fn append(&mut self, trajectory: &Trajectory, style: &TrajectoryStyle) {
+        let points = &trajectory.points;
+        let duration = trajectory.times.last().copied().unwrap_or_default();
+        let faded = |mut color: Color, time: f32| {
+            let fade = if duration > 0.0 { time / duration } else { 0.0 };
+            color.a *= 1.0 + (style.end_alpha - 1.0) * fade;
+            color
+        };
+
+        let colors = trajectory
+            .times
+            .iter()
+            .zip(&trajectory.colors)
+            .map(|(&t, &color)| faded(color, t))
+            .collect_vec();
+
+        if style.ribbon_width > 0.0 {
+            let tangents = (0..points.len())
+                .map(|i| {
+                    (points[(i + 1).min(points.len() - 1)] - points[i.saturating_sub(1)])
+                        .normalize_or_zero()
+                })
+                .collect_vec();
+            let half_width = style.ribbon_width / 2.0;
+
+            // Two triangles per segment
+            for i in 0..points.len() - 1 {
+                let corners = [
+                    (i, -half_width),
+                    (i, half_width),
+                    (i + 1, -half_width),
+                    (i + 1, -half_width),
+                    (i, half_width),
+                    (i + 1, half_width),
+                ];
+
+                for (j, offset) in corners {
+                    self.paths.push(points[j], tangents[j], offset, colors[j]);
+                }
+            }
+        } else {
+            for i in 0..points.len() - 1 {
+                for j in [i, i + 1] {
+                    self.paths.push(points[j], Vec3A::ZERO, 0.0, colors[j]);
+                }
+            }
+        }
+
+        if style.tick_interval > 0.0 {
+            let interval = style.tick_interval.max(duration / MAX_TICKS as f32);
+            let tick_times = (1..)
+                .map(|k| k as f32 * interval)
+                .take_while(|&time| time <= duration);
+
+            for time in tick_times {
+                let (Some(point), Some(ahead)) = (
+                    trajectory.position_at(time),
+                    trajectory.position_at((time + interval * 0.01).min(duration)),
+                ) else {
+                    continue;
+                };
+
+                let tangent = (ahead - point).normalize_or_zero();
+                let color = faded(trajectory.color_at(time), time);
+                for offset in [-style.tick_size / 2.0, style.tick_size / 2.0] {
+                    self.ticks.push(point, tangent, offset, color);
+                }
+            }
+        }
+    }
+
+    /// Appends an encounter marker, a line between both bodies at the closest approach
+    /// with a small cross at either end.
+    fn append_encounter(&mut self, encounter: &Encounter, style: &TrajectoryStyle) {
+        let (a, b) = (encounter.body_position, encounter.target_position);
+        let half_size = style.encounter_marker_size / 2.0;
+
+        let mut lines = vec![(a, b)];
+        for center in [a, b] {
+            lines.extend([Vec3A::X, Vec3A::Y, Vec3A::Z].map(|axis| {
+                let offset = axis * half_size;
+                (center - offset, center + offset)
+            }));
+        }
+
+        for (start, end) in lines {
+            for vertex in [start, end] {
+                self.markers
+                    .push(vertex, Vec3A::ZERO, 0.0, style.encounter_marker_color);
+            }
+        }
+    }
+
+    /// Appends an impact marker, the edges of an octahedron around the merge position.
+    fn append_impact(&mut self, merge: &MergeEvent, style: &TrajectoryStyle) {
+        let half_size = style.impact_marker_size / 2.0;
+        let corners = [Vec3A::X, Vec3A::Y, Vec3A::Z]
+            .into_iter()
+            .flat_map(|axis| [axis, -axis])
+            .map(|dir| merge.position + dir * half_size)
+            .collect_vec();
+
+        // Every pair of corners not opposite of each other
+        for (i, j) in (0..6).tuple_combinations().filter(|&(i, j)| i / 2 != j / 2) {
+            for vertex in [corners[i], corners[j]] {
+                self.markers
+                    .push(vertex, Vec3A::ZERO, 0.0, style.impact_marker_color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    fn style(ribbon_width: f32, tick_interval: f32) -> TrajectoryStyle {
+        TrajectoryStyle {
+            end_alpha: 1.0,
+            ribbon_width,
+            tick_interval,
+            tick_size: 0.5,
+            encounter_marker_size: 1.0,
+            encounter_marker_color: Color::RED,
+            impact_marker_size: 2.0,
+            impact_marker_color: Color::ORANGE,
+        }
+    }
+
+    /// A straight path along X, one unit per second.
+    fn straight_trajectory(n_points: usize) -> Trajectory {
+        let mut trajectory = Trajectory::new(Color::WHITE, Vec3A::ZERO, n_points);
+        for i in 1..n_points {
+            trajectory.push(Vec3A::X * i as f32, i as f32);
+        }
+        trajectory
+    }
+
+    /// A prediction of `bodies` in the inertial frame, without forces or events.
+    fn simulation_info(
+        bodies: &[SimulatedBody],
+        n_steps: usize,
+        generation: u64,
+    ) -> SimulationInfo {
+        SimulationInfo {
+            bodies_sim: bodies.to_vec(),
+            trajectories: bodies
+                .iter()
+                .map(|b| {
+                    (
+                        b.body_instance_id,
+                        Trajectory::new(Color::WHITE, b.pos, n_steps),
+                    )
+                })
+                .collect(),
+            test_particles: Vec::new(),
+            test_particle_trajectories: Vec::new(),
+            frames: FrameTracker::new(bodies, FrameAnchor::Inertial, HashMap::new()),
+            delta: 0.01,
+            start_time: 0.0,
+            grav_const: 1.0,
+            n_steps,
+            merge_on_collision: false,
+            merge_scaler: 1.0,
+            tidal_disruption: false,
+            tidal_locking: 0.0,
+            forces: Vec::new(),
+            maneuvers: Vec::new(),
+            encounter_pairs: Vec::new(),
+            cancel: CancellationToken::new(),
+            generation,
+            progress_interval: 0,
+            vertex_budget: 0,
+            decimation_tolerance: 0.0,
+        }
+    }
+
+    fn two_bodies() -> Vec<SimulatedBody> {
+        vec![
+            SimulatedBody::test(1, 100.0, Vec3A::ZERO, Vec3A::ZERO),
+            SimulatedBody::test(2, 1.0, Vec3A::X * 10.0, Vec3A::Z * 3.0),
+        ]
+    }
+
+    /// Receives results until `done` returns true for one, failing after a few seconds.
+    fn receive_until(
+        worker: &TrajectoryWorker,
+        done: impl Fn(&TrajectoryResult) -> bool,
+    ) -> Vec<TrajectoryResult> {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let mut results = Vec::new();
+
+        while !results.last().is_some_and(&done) {
+            assert!(Instant::now() < deadline, "Worker didn't respond in time");
+            match worker.try_recv() {
+                Ok(result) => results.push(result),
+                Err(_) => std::thread::sleep(Duration::from_millis(1)),
+            }
+        }
+
+        results
+    }
+
+    #[test]
+    fn superseded_predictions_are_dropped() {
+        let worker = GravityController::spawn_trajectory_worker();
+
+        // A long prediction that is still running when the next one is queued
+        let mut stale = simulation_info(&two_bodies(), 10_000_000, 1);
+        stale.progress_interval = 100;
+        let cancel = stale.cancel.clone();
+        worker
+            .send_command(TrajectoryCommand::Calculate(Box::new(stale)))
+            .unwrap();
+        let mut results = receive_until(&worker, |r| r.generation == 1);
+
+        cancel.cancel();
+        let mut bodies = two_bodies();
+        bodies.push(SimulatedBody::test(
+            3,
+            1.0,
+            Vec3A::NEG_X * 10.0,
+            Vec3A::ZERO,
+        ));
+        worker
+            .send_command(TrajectoryCommand::Calculate(Box::new(simulation_info(
+                &bodies, 50, 2,
+            ))))
+            .unwrap();
+        results.extend(receive_until(&worker, |r| r.complete));
+
+        // The cancelled prediction never finishes
+        assert!(results.iter().all(|r| r.generation == 2 || !r.complete));
+        assert!(results.iter().any(|r| r.generation == 1));
+
+        let (complete, partial) = GravityController::latest_predictions(results, 2);
+        assert_eq!(complete.expect("Finished prediction").trajectories.len(), 3);
+        assert!(partial.is_none());
+
+        worker.send_command(TrajectoryCommand::Shutdown).unwrap();
+        worker.join().unwrap();
+    }
+
+    #[test]
+    fn partial_results_before_the_finished_one_are_dropped() {
+        let result = |generation, complete, n_trajectories| TrajectoryResult {
+            prediction: Prediction {
+                trajectories: vec![Trajectory::new(Color::WHITE, Vec3A::ZERO, 1); n_trajectories],
+                ..Default::default()
+            },
+            complete,
+            generation,
+        };
+
+        let (complete, partial) = GravityController::latest_predictions(
+            [result(3, false, 1), result(3, true, 2), result(4, false, 3)],
+            3,
+        );
+        assert_eq!(complete.map(|p| p.trajectories.len()), Some(2));
+        assert!(partial.is_none());
+
+        let (complete, partial) =
+            GravityController::latest_predictions([result(3, true, 2), result(4, false, 3)], 4);
+        assert!(complete.is_none());
+        assert_eq!(partial.map(|p| p.trajectories.len()), Some(3));
+    }
+
+    #[test]
+    fn dead_workers_are_respawned() {
+        let mut worker = TrajectoryWorker::new(|_, _| panic!("Worker failure in test"));
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !worker.is_finished() {
+            assert!(Instant::now() < deadline, "Worker didn't stop");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        GravityController::respawn_trajectory_worker(&mut worker);
+        assert!(!worker.is_finished());
+
+        worker
+            .send_command(TrajectoryCommand::Calculate(Box::new(simulation_info(
+                &two_bodies(),
+                10,
+                1,
+            ))))
+            .unwrap();
+        let results = receive_until(&worker, |r| r.complete);
+        assert_eq!(results.last().unwrap().prediction.trajectories.len(), 2);
+
+        worker.send_command(TrajectoryCommand::Shutdown).unwrap();
+        worker.join().unwrap();
+    }
+
+    #[test]
+    fn lines_and_ribbons_follow_the_path() {
+        let trajectory = straight_trajectory(3);
+
+        let mut lines = TrajectoryGeometry::default();
+        lines.append(&trajectory, &style(0.0, 0.0));
+        assert_eq!(
+            lines.paths.vertices,
+            [Vec3A::ZERO, Vec3A::X, Vec3A::X, Vec3A::X * 2.0]
+        );
+        assert!(lines.paths.offsets.iter().all(|&offset| offset == 0.0));
+        assert!(lines.ticks.vertices.is_empty());
+
+        // Two triangles per segment, expanded by half the width to either side
+        let mut ribbon = TrajectoryGeometry::default();
+        ribbon.append(&trajectory, &style(2.0, 0.0));
+        let paths = &ribbon.paths;
+        assert_eq!(paths.vertices.len(), 12);
+        assert!(paths.tangents.iter().all(|&t| t == Vec3A::X));
+        for (segment, (vertices, offsets)) in paths
+            .vertices
+            .chunks(6)
+            .zip(paths.offsets.chunks(6))
+            .enumerate()
+        {
+            let (start, end) = (Vec3A::X * segment as f32, Vec3A::X * (segment + 1) as f32);
+            assert_eq!(vertices, [start, start, end, end, start, end]);
+            assert_eq!(offsets, [-1.0, 1.0, -1.0, -1.0, 1.0, 1.0]);
+        }
+    }
+
+    #[test]
+    fn ticks_cross_the_path_at_their_interval() {
+        let trajectory = straight_trajectory(5);
+
+        let mut geometry = TrajectoryGeometry::default();
+        geometry.append(&trajectory, &style(0.0, 1.5));
+        let ticks = &geometry.ticks;
+
+        // At 1.5 and 3.0 seconds, interpolated between the points
+        assert_eq!(ticks.vertices, [1.5, 1.5, 3.0, 3.0].map(|x| Vec3A::X * x));
+        assert!(ticks.tangents.iter().all(|t| t.distance(Vec3A::X) < 1e-5));
+        assert_eq!(ticks.offsets, [-0.25, 0.25, -0.25, 0.25]);
+    }
+
+    #[test]
+    fn tick_count_is_capped() {
+        let trajectory = straight_trajectory(100);
+
+        let mut geometry = TrajectoryGeometry::default();
+        geometry.append(&trajectory, &style(0.0, 1e-6));
+
+        let n_ticks = geometry.ticks.vertices.len() / 2;
+        assert!(
+            (MAX_TICKS - 1..=MAX_TICKS).contains(&n_ticks),
+            "{n_ticks} ticks"
+        );
+    }
+
+    #[test]
+    fn displayed_paths_are_decimated_and_faded() {
+        // A full circle, which can't be simplified without error
+        let mut trajectory = Trajectory::new(Color::WHITE, Vec3A::X, 100);
+        for i in 1..100 {
+            let angle = std::f32::consts::TAU * i as f32 / 100.0;
+            trajectory.push(Vec3A::new(angle.cos(), 0.0, angle.sin()), i as f32);
+        }
+        let trajectories = HashMap::from([(InstanceId::from_i64(1), trajectory)]);
+
+        let prediction = GravityController::prediction(&trajectories, &[], &[], &[], 0.0, 10, 0.0);
+        let geometry = TrajectoryGeometry::new(
+            &prediction,
+            &TrajectoryStyle {
+                end_alpha: 0.2,
+                ..style(0.0, 0.0)
+            },
+        );
+
+        // Two vertices per segment between the kept points
+        let colors = &geometry.paths.colors;
+        assert!(colors.len() <= 2 * 9, "{} vertices", colors.len());
+        assert_eq!(colors.first().unwrap().a, 1.0);
+        assert!((colors.last().unwrap().a - 0.2).abs() < 1e-6);
+        assert!(colors.windows(2).all(|pair| pair[1].a <= pair[0].a));
+    }
+
+    #[test]
+    fn empty_predictions_draw_nothing() {
+        let geometry = TrajectoryGeometry::new(
+            &Prediction {
+                trajectories: vec![straight_trajectory(1)],
+                ..Default::default()
+            },
+            &style(1.0, 0.5),
+        );
+        assert!(geometry.is_empty());
+        assert!(
+            !TrajectoryGeometry::new(
+                &Prediction {
+                    trajectories: vec![straight_trajectory(2)],
+                    ..Default::default()
+                },
+                &style(1.0, 0.5),
+            )
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn predicted_merges_end_the_absorbed_trajectory() {
+        let bodies = [
+            SimulatedBody {
+                radius: Some(1.0),
+                ..SimulatedBody::test(1, 100.0, Vec3A::ZERO, Vec3A::ZERO)
+            },
+            SimulatedBody {
+                radius: Some(1.0),
+                ..SimulatedBody::test(2, 1.0, Vec3A::X * 10.0, Vec3A::NEG_X * 20.0)
+            },
+        ];
+        let info = SimulationInfo {
+            merge_on_collision: true,
+            ..simulation_info(&bodies, 100, 1)
+        };
+        let (survivor, absorbed) = (InstanceId::from_i64(1), InstanceId::from_i64(2));
+        let absorbed_start = info.trajectories[&absorbed].points[0];
+
+        let prediction = GravityController::simulate_trajectories_inner(info, |_| {}).unwrap();
+
+        let [merge] = prediction.merges.as_slice() else {
+            panic!("Expected one merge, got {:?}", prediction.merges);
+        };
+        assert_eq!(merge.survivor, survivor);
+        assert_eq!(merge.absorbed, [absorbed]);
+        assert!(
+            merge.time > 0.0 && merge.time < 0.5,
+            "Merged at {}",
+            merge.time
+        );
+
+        // The absorbed body's path ends at the merge, the survivor's continues
+        let path = |start| {
+            prediction
+                .trajectories
+                .iter()
+                .find(|t| t.points[0] == start)
+                .unwrap()
+        };
+        let absorbed_path = path(absorbed_start);
+        assert_eq!(absorbed_path.points.last(), Some(&merge.position));
+        assert!(absorbed_path.times.last().unwrap() < &0.5);
+        assert_eq!(path(Vec3A::ZERO).points.len(), 100);
+
+        // Every edge of the octahedron around the merge
+        let geometry = TrajectoryGeometry::new(&prediction, &style(0.0, 0.0));
+        assert_eq!(geometry.markers.vertices.len(), 2 * 12);
+        assert!(
+            geometry
+                .markers
+                .vertices
+                .iter()
+                .all(|v| (v.distance(merge.position) - 1.0).abs() < 1e-5)
+        );
+    }
+}
@@ -0,0 +1,167 @@
+//! Budgeted simplification of trajectory polylines.
+//!
+//! Predicted trajectories have one point per simulation step, which wastes vertices on
+//! long, straight segments. Points are removed in order of how little they contribute to
+//! the shape of the path, measured as their distance to the segment between their
+//! remaining neighbors. Tight curves like periapsis passes keep their points, while
+//! nearly straight stretches collapse to a few vertices.
+
+use glam::Vec3A;
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+/// Simplifies a polyline to at most `budget` points.
+///
+/// Points deviating less than `tolerance` from the simplified path are always removed,
+/// even when the path fits the budget. The first and last points are always kept.
+///
+/// # Parameters
+/// - `points`: The polyline to simplify
+/// - `budget`: Maximum number of points to keep, 0 for no limit
+/// - `tolerance`: Deviation below which points are removed regardless of the budget
+///
+/// # Returns
+///
+/// The indices of the kept points, in ascending order.
+pub fn decimate(points: &[Vec3A], budget: usize, tolerance: f32) -> Vec<usize> {
+    let n = points.len();
+    if n <= 2 {
+        return (0..n).collect();
+    }
+
+    let budget = match budget {
+        0 => usize::MAX,
+        budget => budget.max(2),
+    };
+
+    let mut prev = (0..n).map(|i| i.saturating_sub(1)).collect::<Vec<_>>();
+    let mut next = (1..=n).collect::<Vec<_>>();
+
+    // Removed points have a NaN error, so their stale candidates are skipped
+    let mut errors = vec![f32::INFINITY; n];
+    let mut candidates = BinaryHeap::new();
+    for i in 1..n - 1 {
+        errors[i] = deviation(points[i - 1], points[i], points[i + 1]);
+        candidates.push(Candidate {
+            error: errors[i],
+            index: i,
+        });
+    }
+
+    let mut remaining = n;
+    while let Some(Candidate { error, index }) = candidates.pop() {
+        if error != errors[index] {
+            continue;
+        }
+        if remaining <= budget && error >= tolerance {
+            break;
+        }
+
+        let (p, q) = (prev[index], next[index]);
+        next[p] = q;
+        prev[q] = p;
+        errors[index] = f32::NAN;
+        remaining -= 1;
+
+        // Neighbors inherit the error of the removed point, so the error of the
+        // simplified path never decreases
+        for j in [p, q].into_iter().filter(|&j| j != 0 && j != n - 1) {
+            errors[j] = deviation(points[prev[j]], points[j], points[next[j]]).max(error);
+            candidates.push(Candidate {
+                error: errors[j],
+                index: j,
+            });
+        }
+    }
+
+    let mut kept = Vec::with_capacity(remaining);
+    let mut i = 0;
+    while i < n {
+        kept.push(i);
+        i = next[i];
+    }
+
+    kept
+}
+
+/// Distance of `point` from the segment between `a` and `b`.
+#[inline]
+fn deviation(a: Vec3A, point: Vec3A, b: Vec3A) -> f32 {
+    let segment = b - a;
+    let length_sq = segment.length_squared();
+    if length_sq == 0.0 {
+        return point.distance(a);
+    }
+
+    let t = ((point - a).dot(segment) / length_sq).clamp(0.0, 1.0);
+    point.distance(a + segment * t)
+}
+
+/// A point that may be removed, ordered so the smallest error is popped first.
+struct Candidate {
+    error: f32,
+    index: usize,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .error
+            .total_cmp(&self.error)
+            .then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_corners_and_respects_budget() {
+        // A straight line followed by a sharp turn
+        let points = (0..=100)
+            .map(|i| Vec3A::new(i as f32, 0.0, 0.0))
+            .chain((1..=100).map(|i| Vec3A::new(100.0, i as f32, 0.0)))
+            .collect::<Vec<_>>();
+
+        assert_eq!(decimate(&points, 0, 1e-3), vec![0, 100, 200]);
+
+        let kept = decimate(&points, 50, 0.0);
+        assert_eq!(kept.len(), 50);
+        assert!(kept.contains(&100));
+        assert_eq!((kept[0], kept[49]), (0, 200));
+    }
+
+    #[test]
+    fn curves_keep_more_points_than_lines() {
+        // Half a circle of radius 10, then a long straight segment
+        let points = (0..=200)
+            .map(|i| {
+                let angle = std::f32::consts::PI * i as f32 / 200.0;
+                Vec3A::new(angle.cos(), angle.sin(), 0.0) * 10.0
+            })
+            .chain((1..=200).map(|i| Vec3A::new(-10.0, -(i as f32), 0.0)))
+            .collect::<Vec<_>>();
+
+        let kept = decimate(&points, 40, 0.0);
+        let on_circle = kept.iter().filter(|&&i| i <= 200).count();
+        assert!(
+            on_circle > 30,
+            "{on_circle} of {} points on the circle",
+            kept.len()
+        );
+    }
+}
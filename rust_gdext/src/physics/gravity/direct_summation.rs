@@ -1,4 +1,6 @@
-use super::{GRAVITATIONAL_SOFTENING_SQUARED, NBodyGravityCalculator, PosMass, merge_radius};
+use super::{
+    GRAVITATIONAL_SOFTENING_SQUARED, HasOblateness, HasVelocity, NBodyGravityCalculator, PosMass,
+};
 use glam::Vec3A;
 use godot::builtin::math::FloatExt;
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator}; // Added import
@@ -15,7 +17,7 @@ impl<'a, T> DirectSummation<'a, T> {
 
 impl<'a, T> NBodyGravityCalculator<T> for DirectSummation<'a, T>
 where
-    T: PosMass + Sync,
+    T: PosMass + HasOblateness + Sync,
 {
     fn calc_accs<const PARALLEL: bool>(&self, g: f32) -> Vec<Vec3A> {
         let particles = self.particles;
@@ -38,39 +40,191 @@ where
         }
     }
 
-    fn detect_collisions(&self, merge_scaler: f32) -> Vec<(usize, usize)> {
+    fn calc_accs_at<const PARALLEL: bool>(&self, g: f32, points: &[Vec3A]) -> Vec<Vec3A> {
         let particles = self.particles;
 
-        let mut collisions = Vec::new();
-        let len = particles.len();
-        for i in 0..len {
-            for j in (i + 1)..len {
-                let a = &particles[i];
-                let b = &particles[j];
-                if a.get_pos().distance(b.get_pos())
-                    < merge_radius(merge_scaler, a.get_mass(), b.get_mass())
-                {
-                    collisions.push((i, j));
-                }
-            }
+        if PARALLEL {
+            points
+                .par_iter()
+                .map(|&point| calc_acc_at(g, point, particles))
+                .collect()
+        } else {
+            points
+                .iter()
+                .map(|&point| calc_acc_at(g, point, particles))
+                .collect()
         }
+    }
+
+    fn calc_potentials_at<const PARALLEL: bool>(&self, g: f32, points: &[Vec3A]) -> Vec<f32> {
+        let particles = self.particles;
 
-        collisions
+        if PARALLEL {
+            points
+                .par_iter()
+                .map(|&point| calc_potential_at(g, point, particles))
+                .collect()
+        } else {
+            points
+                .iter()
+                .map(|&point| calc_potential_at(g, point, particles))
+                .collect()
+        }
     }
 }
 
-fn calc_acc<T: PosMass>(g: f32, body: &T, bodies: &[T]) -> Vec3A {
+impl<T> DirectSummation<'_, T>
+where
+    T: PosMass + HasVelocity,
+{
+    /// Adds the first post-Newtonian (1PN) correction of general relativity to `accs`.
+    ///
+    /// Uses the 1PN relative acceleration of each pair in harmonic coordinates, which makes
+    /// orbits precess, and splits it between the pair by their masses so momentum is
+    /// conserved. Only pairs of bodies with at least `mass_threshold` mass interact, as the
+    /// correction is negligible for everything else.
+    ///
+    /// # Parameters
+    /// - `g`: The gravitational constant
+    /// - `speed_of_light`: The speed of light in simulation units
+    /// - `mass_threshold`: Minimum mass of both bodies of a pair
+    /// - `accs`: Accelerations indexed like the particles, updated in-place
+    pub fn add_post_newtonian_accs(
+        &self,
+        g: f32,
+        speed_of_light: f32,
+        mass_threshold: f32,
+        accs: &mut [Vec3A],
+    ) {
+        let heavy = self
+            .particles
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| p.get_mass() >= mass_threshold && p.get_mass() > 0.0)
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+
+        let c_sq = speed_of_light * speed_of_light;
+
+        for (k, &i) in heavy.iter().enumerate() {
+            for &j in &heavy[k + 1..] {
+                let (a, b) = (&self.particles[i], &self.particles[j]);
+
+                let diff = a.get_pos() - b.get_pos();
+                let r_sq = diff.length_squared() + GRAVITATIONAL_SOFTENING_SQUARED;
+                let r = r_sq.sqrt();
+                let n = diff / r;
+                let v = a.get_vel() - b.get_vel();
+                let r_dot = n.dot(v);
+
+                let total_mass = a.get_mass() + b.get_mass();
+                let eta = a.get_mass() * b.get_mass() / (total_mass * total_mass);
+                let gm = g * total_mass;
+
+                let relative = gm / (c_sq * r_sq)
+                    * (n * ((4.0 + 2.0 * eta) * gm / r - (1.0 + 3.0 * eta) * v.length_squared()
+                        + 1.5 * eta * r_dot * r_dot)
+                        + v * ((4.0 - 2.0 * eta) * r_dot));
+
+                accs[i] += relative * (b.get_mass() / total_mass);
+                accs[j] -= relative * (a.get_mass() / total_mass);
+            }
+        }
+    }
+}
+
+#[inline]
+fn calc_acc<T: PosMass + HasOblateness>(g: f32, body: &T, bodies: &[T]) -> Vec3A {
+    calc_acc_at(g, body.get_pos(), bodies) + harmonics_reaction(g, body, bodies)
+}
+
+/// Reaction of the zonal harmonics of `body` pulling on `bodies`.
+///
+/// A non-spherical body accelerates the others by its harmonics, and by Newton's third
+/// law is pulled back by `-m_other / m_body` times that acceleration, so momentum is
+/// conserved. Zero for point masses.
+fn harmonics_reaction<T: PosMass + HasOblateness>(g: f32, body: &T, bodies: &[T]) -> Vec3A {
+    let Some(harmonics) = body.get_zonal_harmonics() else {
+        return Vec3A::ZERO;
+    };
+
     bodies
         .iter()
         .map(|other| (other.get_pos() - body.get_pos(), other.get_mass()))
         .filter(|(diff, _)| !diff.length_squared().is_zero_approx())
-        .map(|(diff, other_mass)| {
+        .map(|(diff, other_mass)| -harmonics.acceleration(g * other_mass, diff))
+        .sum()
+}
+
+/// Acceleration at `point` caused by `bodies`, skipping bodies located exactly at `point`.
+fn calc_acc_at<T: PosMass + HasOblateness>(g: f32, point: Vec3A, bodies: &[T]) -> Vec3A {
+    bodies
+        .iter()
+        .map(|other| (other, other.get_pos() - point, other.get_mass()))
+        .filter(|(_, diff, _)| !diff.length_squared().is_zero_approx())
+        .map(|(other, diff, other_mass)| {
             let r2 = diff.length_squared();
             let r2_softened = r2 + GRAVITATIONAL_SOFTENING_SQUARED; // Apply softening using common constant
 
             let inv_r_softened_cubed = r2_softened.powf(-1.5); // 1 / (r_softened^3/2)
 
-            diff * inv_r_softened_cubed * g * other_mass
+            let acc = diff * inv_r_softened_cubed * g * other_mass;
+
+            // Non-spherical bodies add their zonal harmonics
+            match other.get_zonal_harmonics() {
+                Some(harmonics) => acc + harmonics.acceleration(g * other_mass, -diff),
+                None => acc,
+            }
         })
         .sum()
 }
+
+/// Potential at `point` caused by `bodies`, skipping bodies located exactly at `point`.
+fn calc_potential_at<T: PosMass + HasOblateness>(g: f32, point: Vec3A, bodies: &[T]) -> f32 {
+    bodies
+        .iter()
+        .map(|other| (other, other.get_pos() - point, other.get_mass()))
+        .filter(|(_, diff, _)| !diff.length_squared().is_zero_approx())
+        .map(|(other, diff, other_mass)| {
+            let r_softened = (diff.length_squared() + GRAVITATIONAL_SOFTENING_SQUARED).sqrt();
+            let potential = -g * other_mass / r_softened;
+
+            match other.get_zonal_harmonics() {
+                Some(harmonics) => potential + harmonics.potential(g * other_mass, -diff),
+                None => potential,
+            }
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::gravity::controller::SimulatedBody;
+
+    #[test]
+    fn zonal_harmonics_conserve_momentum() {
+        let body = |id, mass, pos, radius, zonal_harmonics| SimulatedBody {
+            angular_velocity: Vec3A::new(0.1, 1.0, 0.0),
+            radius,
+            zonal_harmonics,
+            ..SimulatedBody::test(id, mass, pos, Vec3A::ZERO)
+        };
+        let bodies = [
+            body(1, 1000.0, Vec3A::ZERO, Some(2.0), [0.05, 0.01, -0.02]),
+            body(2, 10.0, Vec3A::new(6.0, 3.0, 0.0), Some(1.0), [0.0; 3]),
+            body(3, 1.0, Vec3A::new(-2.0, -4.0, 5.0), None, [0.0; 3]),
+        ];
+
+        let total_force = |accs: Vec<Vec3A>| {
+            accs.iter()
+                .zip(&bodies)
+                .map(|(acc, body)| *acc * body.mass)
+                .sum::<Vec3A>()
+        };
+
+        let momentum = total_force(DirectSummation::new(&bodies).calc_accs::<false>(1.0));
+
+        assert!(momentum.length() < 1e-3, "{momentum}");
+    }
+}
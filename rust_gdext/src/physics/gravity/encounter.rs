@@ -0,0 +1,142 @@
+//! Closest approaches between predicted trajectories.
+//!
+//! An encounter is a local minimum of the separation of two bodies over the prediction,
+//! like the periapsis of a flyby or the moment an intercept gets closest to its target.
+//! Minima are searched on the separations at every simulation step and refined between
+//! the steps by fitting a parabola through the squared separations around them.
+
+use glam::Vec3A;
+use godot::obj::InstanceId;
+
+/// A local minimum of the separation between two bodies.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Encounter {
+    pub body: InstanceId,
+
+    pub target: InstanceId,
+
+    /// Simulation time of the closest approach
+    pub time: f64,
+
+    /// Separation at the closest approach
+    pub distance: f32,
+
+    /// Positions of both bodies at the closest approach, in trajectory space
+    pub body_position: Vec3A,
+    pub target_position: Vec3A,
+}
+
+/// Finds the encounters between two trajectories sampled at the same `times`.
+///
+/// The separations are passed separately, as the trajectories may be drawn in different
+/// frames. The start and end of the prediction are not encounters, even if the bodies
+/// are closest there. Trajectories of different lengths, like those of merged bodies,
+/// are compared as long as both last.
+///
+/// # Parameters
+/// - `start_time`: Simulation time at the start of the prediction
+/// - `times`: Time of each point since the start of the prediction
+/// - `distances`: Separation of the bodies at `times`
+/// - `body_points`, `target_points`: Positions of both bodies at `times`, as drawn
+pub fn find_encounters(
+    body: InstanceId,
+    target: InstanceId,
+    start_time: f64,
+    times: &[f32],
+    distances: &[f32],
+    body_points: &[Vec3A],
+    target_points: &[Vec3A],
+) -> Vec<Encounter> {
+    let n = times
+        .len()
+        .min(distances.len())
+        .min(body_points.len())
+        .min(target_points.len());
+
+    (1..n.saturating_sub(1))
+        .filter(|&i| distances[i - 1] > distances[i] && distances[i] <= distances[i + 1])
+        .map(|i| {
+            // Squared separations are exactly parabolic for bodies moving in straight lines
+            let [d0, d1, d2] = [i - 1, i, i + 1].map(|j| distances[j].powi(2));
+
+            // Vertex of the parabola through the three separations, in steps from `i`
+            let curvature = d0 - 2.0 * d1 + d2;
+            let offset = if curvature > 0.0 {
+                (0.5 * (d0 - d2) / curvature).clamp(-0.5, 0.5)
+            } else {
+                0.0
+            };
+
+            let (j, t) = if offset < 0.0 {
+                (i - 1, -offset)
+            } else {
+                (i + 1, offset)
+            };
+            let body_position = body_points[i].lerp(body_points[j], t);
+            let target_position = target_points[i].lerp(target_points[j], t);
+            let time = times[i] + (times[j] - times[i]) * t;
+
+            Encounter {
+                body,
+                target,
+                time: start_time + f64::from(time),
+                distance: (d1 - 0.25 * (d0 - d2) * offset).max(0.0).sqrt(),
+                body_position,
+                target_position,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_minimum_between_steps() {
+        // Passing a static target at a distance of 1, closest at x = 0.3
+        let times = (0..=20).map(|i| i as f32).collect::<Vec<_>>();
+        let body_points = times
+            .iter()
+            .map(|&t| Vec3A::new(t - 10.0, 0.0, 0.0))
+            .collect::<Vec<_>>();
+        let target_points = vec![Vec3A::new(0.3, 1.0, 0.0); times.len()];
+        let distances = body_points
+            .iter()
+            .zip(&target_points)
+            .map(|(a, b)| a.distance(*b))
+            .collect::<Vec<_>>();
+
+        let (body, target) = (InstanceId::from_i64(1), InstanceId::from_i64(2));
+        let encounters = find_encounters(
+            body,
+            target,
+            100.0,
+            &times,
+            &distances,
+            &body_points,
+            &target_points,
+        );
+
+        assert_eq!(encounters.len(), 1);
+        let encounter = encounters[0];
+        assert!((encounter.time - 110.3).abs() < 1e-3, "{}", encounter.time);
+        assert!(
+            (encounter.distance - 1.0).abs() < 1e-3,
+            "{}",
+            encounter.distance
+        );
+
+        // Receding bodies have no encounter
+        let receding = find_encounters(
+            body,
+            target,
+            0.0,
+            &times[10..],
+            &distances[10..],
+            &body_points[10..],
+            &target_points[10..],
+        );
+        assert!(receding.is_empty());
+    }
+}
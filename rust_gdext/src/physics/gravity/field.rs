@@ -0,0 +1,217 @@
+//! Evaluation of the gravity field away from the bodies.
+//!
+//! The field can be evaluated at arbitrary points, or sampled on a regular grid into a
+//! single-channel float `Image` (`FORMAT_RF`) or `ImageTexture3D`. The textures are meant
+//! for overlays such as gravity wells and for debugging orbits in the editor.
+
+use super::{HasOblateness, NBodyGravityCalculator, PosMass, direct_summation::DirectSummation};
+use crate::{octree::morton_based::MortonBasedOctree, to_glam_vec3};
+use glam::Vec3A;
+use godot::{
+    classes::{Image, ImageTexture3D, image::Format},
+    global::Error,
+    prelude::*,
+};
+
+/// Quantity of the gravity field to sample, as selected in the editor.
+#[derive(GodotConvert, Var, Export, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[godot(via = i64)]
+pub enum FieldQuantity {
+    /// Gravitational potential, negative and deepest at the bodies
+    #[default]
+    Potential,
+
+    /// Length of the gravitational acceleration
+    AccelerationMagnitude,
+}
+
+/// Calculates the gravitational accelerations at `points` caused by `sources`.
+///
+/// Picks the algorithm by the number of sources and points, like the simulation does
+/// for the bodies themselves.
+pub fn accelerations_at<T>(grav_const: f32, sources: &[T], points: &[Vec3A]) -> Vec<Vec3A>
+where
+    T: PosMass + HasOblateness + Sync,
+{
+    match (sources.len(), points.len()) {
+        // Thresholds follow the ones used for the massive bodies
+        //     Sources   Points         Algorithm                    Parallel
+        (..440, ..100) => DirectSummation::new(sources).calc_accs_at::<false>(grav_const, points),
+        (..440, 100..) => DirectSummation::new(sources).calc_accs_at::<true>(grav_const, points),
+        (440.., _) => MortonBasedOctree::new(sources).calc_accs_at::<true>(grav_const, points),
+    }
+}
+
+/// Calculates the gravitational potentials at `points` caused by `sources`.
+pub fn potentials_at<T>(grav_const: f32, sources: &[T], points: &[Vec3A]) -> Vec<f32>
+where
+    T: PosMass + HasOblateness + Sync,
+{
+    match (sources.len(), points.len()) {
+        //     Sources   Points         Algorithm                    Parallel
+        (..440, ..100) => {
+            DirectSummation::new(sources).calc_potentials_at::<false>(grav_const, points)
+        }
+        (..440, 100..) => {
+            DirectSummation::new(sources).calc_potentials_at::<true>(grav_const, points)
+        }
+        (440.., _) => {
+            MortonBasedOctree::new(sources).calc_potentials_at::<true>(grav_const, points)
+        }
+    }
+}
+
+/// Evaluates `quantity` at `points`.
+pub fn sample_field<T>(
+    grav_const: f32,
+    sources: &[T],
+    quantity: FieldQuantity,
+    points: &[Vec3A],
+) -> Vec<f32>
+where
+    T: PosMass + HasOblateness + Sync,
+{
+    match quantity {
+        FieldQuantity::Potential => potentials_at(grav_const, sources, points),
+        FieldQuantity::AccelerationMagnitude => accelerations_at(grav_const, sources, points)
+            .into_iter()
+            .map(Vec3A::length)
+            .collect(),
+    }
+}
+
+/// Samples `quantity` on the parallelogram spanned by `axis_u` and `axis_v` from `origin`.
+///
+/// Pixel `(x, y)` holds the value at the center of its cell, so the image covers the
+/// slice exactly. Returns `None` for an empty resolution.
+pub fn sample_slice<T>(
+    grav_const: f32,
+    sources: &[T],
+    quantity: FieldQuantity,
+    origin: Vector3,
+    axis_u: Vector3,
+    axis_v: Vector3,
+    resolution: Vector2i,
+) -> Option<Gd<Image>>
+where
+    T: PosMass + HasOblateness + Sync,
+{
+    let (width, height) = (resolution.x, resolution.y);
+    if width <= 0 || height <= 0 {
+        return None;
+    }
+
+    let (origin, u, v) = (
+        to_glam_vec3(origin),
+        to_glam_vec3(axis_u),
+        to_glam_vec3(axis_v),
+    );
+    let points = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            origin + u * ((x as f32 + 0.5) / width as f32) + v * ((y as f32 + 0.5) / height as f32)
+        })
+        .collect::<Vec<_>>();
+
+    let values = sample_field(grav_const, sources, quantity, &points);
+    float_image(width, height, &values)
+}
+
+/// Samples `quantity` on a regular grid filling `bounds`, one image layer per Z slice.
+///
+/// Returns `None` for an empty resolution.
+pub fn sample_volume<T>(
+    grav_const: f32,
+    sources: &[T],
+    quantity: FieldQuantity,
+    bounds: Aabb,
+    resolution: Vector3i,
+) -> Option<Gd<ImageTexture3D>>
+where
+    T: PosMass + HasOblateness + Sync,
+{
+    let (width, height, depth) = (resolution.x, resolution.y, resolution.z);
+    if width <= 0 || height <= 0 || depth <= 0 {
+        return None;
+    }
+
+    let (position, size) = (to_glam_vec3(bounds.position), to_glam_vec3(bounds.size));
+    let cells = Vec3A::new(width as f32, height as f32, depth as f32);
+    let points = (0..depth)
+        .flat_map(|z| (0..height).flat_map(move |y| (0..width).map(move |x| (x, y, z))))
+        .map(|(x, y, z)| {
+            let cell = Vec3A::new(x as f32, y as f32, z as f32) + 0.5;
+            position + size * cell / cells
+        })
+        .collect::<Vec<_>>();
+
+    let values = sample_field(grav_const, sources, quantity, &points);
+    let layers = values
+        .chunks_exact(width as usize * height as usize)
+        .map(|layer| float_image(width, height, layer))
+        .collect::<Option<Array<_>>>()?;
+
+    let mut texture = ImageTexture3D::new_gd();
+    match texture.create(Format::RF, width, height, depth, false, &layers) {
+        Error::OK => Some(texture),
+        error => {
+            godot_error!("Failed to create the field texture: {:?}", error);
+            None
+        }
+    }
+}
+
+/// Packs row-major values into a single-channel float image.
+fn float_image(width: i32, height: i32, values: &[f32]) -> Option<Gd<Image>> {
+    let data = values
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect::<PackedByteArray>();
+
+    Image::create_from_data(width, height, false, Format::RF, &data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::gravity::controller::SimulatedBody;
+
+    #[test]
+    fn acceleration_is_gradient_of_potential() {
+        let sources = (0..600)
+            .map(|i| {
+                let t = i as f32;
+                SimulatedBody::test(
+                    i + 1,
+                    1.0 + (t * 0.37).fract(),
+                    Vec3A::new((t * 1.3).sin(), (t * 0.7).cos(), (t * 0.11).sin()) * 20.0,
+                    Vec3A::ZERO,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let point = Vec3A::new(60.0, -10.0, 25.0);
+        let eps = 0.05;
+        let offsets = [Vec3A::X, Vec3A::Y, Vec3A::Z].map(|d| d * eps);
+        let samples = offsets
+            .iter()
+            .flat_map(|&d| [point + d, point - d])
+            .collect::<Vec<_>>();
+
+        // Both the direct sum and the octree
+        for sources in [&sources[..400], &sources[..]] {
+            let potentials = potentials_at(1.0, sources, &samples);
+            let gradient = Vec3A::new(
+                potentials[0] - potentials[1],
+                potentials[2] - potentials[3],
+                potentials[4] - potentials[5],
+            ) / (2.0 * eps);
+
+            let acc = accelerations_at(1.0, sources, &[point])[0];
+            assert!(
+                (acc + gradient).length() < 0.05 * acc.length(),
+                "{acc} vs {gradient}"
+            );
+        }
+    }
+}
@@ -0,0 +1,367 @@
+//! Non-gravitational forces acting on simulated bodies.
+//!
+//! Every force implements [`ForceGenerator`], which adds an acceleration to each affected
+//! body once per simulation step, on top of the n-body gravity. Bodies are referred to by
+//! instance ID, so generators stay valid when bodies merge or are removed.
+//!
+//! The built-in generators are configured through properties on [`GravityBody`]:
+//!
+//! - [`ConstantThrust`]: a constant force on a single body
+//! - [`AtmosphericDrag`]: linear and quadratic drag on bodies close to a body
+//! - [`RadiationPressure`]: an inverse-square push away from a luminous body
+//! - [`CallableForce`]: a force computed by a Godot `Callable`
+//!
+//! [`PostNewtonianCorrection`] is configured on the [`GravityController`] instead.
+//!
+//! [`GravityBody`]: super::body::GravityBody
+//! [`GravityController`]: super::controller::GravityController
+
+use super::{
+    HasMass, HasPosition, HasVelocity, controller::SimulatedBody, direct_summation::DirectSummation,
+};
+use crate::{from_glam_vec3, to_glam_vec3};
+use glam::Vec3A;
+use godot::prelude::*;
+use std::collections::HashMap;
+
+/// A source of non-gravitational acceleration.
+pub trait ForceGenerator {
+    /// Adds the accelerations caused by this force to `accelerations`,
+    /// which is indexed like `bodies`.
+    fn add_accelerations(&self, bodies: &[SimulatedBody], accelerations: &mut [Vec3A]);
+}
+
+/// Finds the index of the body with the given instance ID.
+#[inline]
+fn index_of(bodies: &[SimulatedBody], id: InstanceId) -> Option<usize> {
+    bodies.iter().position(|b| b.body_instance_id == id)
+}
+
+/// A constant force, like engine thrust, acting on a single body.
+#[derive(Clone, Debug)]
+pub struct ConstantThrust {
+    pub body: InstanceId,
+    pub force: Vec3A,
+}
+
+impl ForceGenerator for ConstantThrust {
+    fn add_accelerations(&self, bodies: &[SimulatedBody], accelerations: &mut [Vec3A]) {
+        let Some(i) = index_of(bodies, self.body) else {
+            return;
+        };
+
+        let mass = bodies[i].get_mass();
+        if mass > 0.0 {
+            accelerations[i] += self.force / mass;
+        }
+    }
+}
+
+/// Drag on every body within `range` of an atmosphere-carrying body.
+///
+/// The drag depends on the velocity relative to the source body:
+/// `a = -(linear * v + quadratic * |v| * v)`.
+#[derive(Clone, Debug)]
+pub struct AtmosphericDrag {
+    pub source: InstanceId,
+    pub range: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+impl ForceGenerator for AtmosphericDrag {
+    fn add_accelerations(&self, bodies: &[SimulatedBody], accelerations: &mut [Vec3A]) {
+        let Some(source_idx) = index_of(bodies, self.source) else {
+            return;
+        };
+        let source = &bodies[source_idx];
+        let range_sq = self.range * self.range;
+
+        for (i, (body, acc)) in bodies.iter().zip(accelerations).enumerate() {
+            if i == source_idx || body.get_pos().distance_squared(source.get_pos()) > range_sq {
+                continue;
+            }
+
+            let rel_vel = body.get_vel() - source.get_vel();
+            *acc -= rel_vel * (self.linear + self.quadratic * rel_vel.length());
+        }
+    }
+}
+
+/// Radiation pressure from a luminous body acting on the bodies around it.
+///
+/// Pushes each target directly away from the source with `a = luminosity * coefficient / r²`,
+/// where `coefficient` is the target's radiation pressure coefficient.
+#[derive(Clone, Debug)]
+pub struct RadiationPressure {
+    pub source: InstanceId,
+    pub luminosity: f32,
+
+    /// Affected bodies and their radiation pressure coefficients
+    pub targets: Vec<(InstanceId, f32)>,
+}
+
+impl ForceGenerator for RadiationPressure {
+    fn add_accelerations(&self, bodies: &[SimulatedBody], accelerations: &mut [Vec3A]) {
+        let indices = bodies
+            .iter()
+            .enumerate()
+            .map(|(i, b)| (b.body_instance_id, i))
+            .collect::<HashMap<_, _>>();
+        let Some(&source_idx) = indices.get(&self.source) else {
+            return;
+        };
+        let source_pos = bodies[source_idx].get_pos();
+
+        for &(target, coefficient) in &self.targets {
+            let Some(&i) = indices.get(&target) else {
+                continue;
+            };
+
+            let diff = bodies[i].get_pos() - source_pos;
+            let dist_sq = diff.length_squared();
+            if dist_sq > 0.0 {
+                accelerations[i] +=
+                    diff * (self.luminosity * coefficient / (dist_sq * dist_sq.sqrt()));
+            }
+        }
+    }
+}
+
+/// A force computed by a Godot `Callable` on a single body.
+///
+/// The callable is called with the body's position and velocity and must return the
+/// force as a `Vector3`. Callables can only be called from the main thread, so trajectory
+/// predictions use [`CallableForce::sample`] instead, which holds the force constant.
+///
+/// The callable must not access the `GravityController`, which is busy simulating.
+pub struct CallableForce {
+    pub body: InstanceId,
+    pub callable: Callable,
+}
+
+impl CallableForce {
+    fn call(&self, body: &SimulatedBody) -> Vec3A {
+        let force = self.callable.callv(&varray![
+            from_glam_vec3(body.get_pos()),
+            from_glam_vec3(body.get_vel()),
+        ]);
+
+        match force.try_to::<Vector3>() {
+            Ok(force) => to_glam_vec3(force),
+            Err(_) => {
+                godot_error!("Force callable must return a Vector3, got {}", force);
+                Vec3A::ZERO
+            }
+        }
+    }
+
+    /// Evaluates the callable once, returning its force as a constant thrust.
+    pub fn sample(&self, bodies: &[SimulatedBody]) -> ConstantThrust {
+        ConstantThrust {
+            body: self.body,
+            force: index_of(bodies, self.body)
+                .map(|i| self.call(&bodies[i]))
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl ForceGenerator for CallableForce {
+    fn add_accelerations(&self, bodies: &[SimulatedBody], accelerations: &mut [Vec3A]) {
+        let Some(i) = index_of(bodies, self.body) else {
+            return;
+        };
+
+        let mass = bodies[i].get_mass();
+        if mass > 0.0 {
+            accelerations[i] += self.call(&bodies[i]) / mass;
+        }
+    }
+}
+
+/// The first post-Newtonian correction of general relativity between massive bodies.
+///
+/// Makes the orbits around compact massive bodies precess, like Mercury's perihelion.
+/// Only pairs of bodies with at least `mass_threshold` mass are corrected, see
+/// [`DirectSummation::add_post_newtonian_accs`].
+#[derive(Clone, Debug)]
+pub struct PostNewtonianCorrection {
+    pub grav_const: f32,
+    pub speed_of_light: f32,
+    pub mass_threshold: f32,
+}
+
+impl ForceGenerator for PostNewtonianCorrection {
+    fn add_accelerations(&self, bodies: &[SimulatedBody], accelerations: &mut [Vec3A]) {
+        if self.speed_of_light <= 0.0 {
+            return;
+        }
+
+        DirectSummation::new(bodies).add_post_newtonian_accs(
+            self.grav_const,
+            self.speed_of_light,
+            self.mass_threshold,
+            accelerations,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::gravity::controller::GravityController;
+
+    #[test]
+    fn post_newtonian_conserves_momentum_and_skips_light_bodies() {
+        let bodies = [
+            SimulatedBody::test(1, 1000.0, Vec3A::ZERO, Vec3A::new(0.0, -1.0, 0.0)),
+            SimulatedBody::test(2, 500.0, Vec3A::X * 10.0, Vec3A::new(0.5, 2.0, 0.0)),
+            SimulatedBody::test(3, 1.0, Vec3A::Z * 10.0, Vec3A::ZERO),
+        ];
+        let mut accs = vec![Vec3A::ZERO; bodies.len()];
+
+        PostNewtonianCorrection {
+            grav_const: 1.0,
+            speed_of_light: 50.0,
+            mass_threshold: 100.0,
+        }
+        .add_accelerations(&bodies, &mut accs);
+
+        let momentum = accs[0] * bodies[0].mass + accs[1] * bodies[1].mass;
+        assert!(momentum.length() < 1e-4 * accs[0].length() * bodies[0].mass);
+        assert_ne!(accs[0], Vec3A::ZERO);
+        assert_eq!(accs[2], Vec3A::ZERO);
+    }
+
+    #[test]
+    fn post_newtonian_weakens_circular_orbits() {
+        // In harmonic coordinates the 1PN term of a circular orbit points outward
+        let radius = 10.0;
+        let bodies = [
+            SimulatedBody::test(1, 1000.0, Vec3A::ZERO, Vec3A::ZERO),
+            SimulatedBody::test(
+                2,
+                100.0,
+                Vec3A::X * radius,
+                Vec3A::Y * (1100.0f32 / radius).sqrt(),
+            ),
+        ];
+        let mut accs = vec![Vec3A::ZERO; bodies.len()];
+
+        PostNewtonianCorrection {
+            grav_const: 1.0,
+            speed_of_light: 100.0,
+            mass_threshold: 100.0,
+        }
+        .add_accelerations(&bodies, &mut accs);
+
+        assert!(accs[1].x > 0.0 && accs[1].y.abs() < 1e-6);
+    }
+
+    fn id(id: i64) -> InstanceId {
+        InstanceId::from_i64(id)
+    }
+
+    fn accelerations(force: &dyn ForceGenerator, bodies: &[SimulatedBody]) -> Vec<Vec3A> {
+        let mut accs = vec![Vec3A::ZERO; bodies.len()];
+        force.add_accelerations(bodies, &mut accs);
+        accs
+    }
+
+    #[test]
+    fn thrust_only_affects_its_target() {
+        let bodies = [
+            SimulatedBody::test(1, 2.0, Vec3A::ZERO, Vec3A::ZERO),
+            SimulatedBody::test(2, 4.0, Vec3A::X, Vec3A::ZERO),
+        ];
+
+        let thrust = ConstantThrust {
+            body: id(2),
+            force: Vec3A::new(0.0, 8.0, 0.0),
+        };
+        assert_eq!(
+            accelerations(&thrust, &bodies),
+            [Vec3A::ZERO, Vec3A::Y * 2.0]
+        );
+
+        // Targets that are gone are ignored
+        let thrust = ConstantThrust {
+            body: id(3),
+            ..thrust
+        };
+        assert_eq!(accelerations(&thrust, &bodies), [Vec3A::ZERO; 2]);
+    }
+
+    #[test]
+    fn drag_opposes_relative_velocity_within_range() {
+        let planet_vel = Vec3A::new(1.0, 0.0, 0.0);
+        let bodies = [
+            SimulatedBody::test(1, 100.0, Vec3A::ZERO, planet_vel),
+            SimulatedBody::test(2, 1.0, Vec3A::Y * 5.0, Vec3A::new(1.0, 3.0, 4.0)),
+            SimulatedBody::test(3, 1.0, Vec3A::Y * 20.0, Vec3A::new(1.0, 3.0, 4.0)),
+        ];
+        let rel_vel = bodies[1].vel - planet_vel;
+
+        let drag = |linear, quadratic| AtmosphericDrag {
+            source: id(1),
+            range: 10.0,
+            linear,
+            quadratic,
+        };
+
+        let linear = accelerations(&drag(0.5, 0.0), &bodies);
+        assert_eq!(linear, [Vec3A::ZERO, -0.5 * rel_vel, Vec3A::ZERO]);
+
+        // |v| = 5
+        let quadratic = accelerations(&drag(0.0, 0.1), &bodies);
+        assert!(quadratic[1].distance(-0.5 * rel_vel) < 1e-6);
+        assert_eq!(quadratic[2], Vec3A::ZERO);
+    }
+
+    #[test]
+    fn radiation_pressure_falls_off_with_distance() {
+        let bodies = [
+            SimulatedBody::test(1, 100.0, Vec3A::ZERO, Vec3A::ZERO),
+            SimulatedBody::test(2, 1.0, Vec3A::X * 2.0, Vec3A::ZERO),
+            SimulatedBody::test(3, 1.0, Vec3A::NEG_Z * 4.0, Vec3A::ZERO),
+            SimulatedBody::test(4, 1.0, Vec3A::Y * 2.0, Vec3A::ZERO),
+        ];
+
+        let pressure = RadiationPressure {
+            source: id(1),
+            luminosity: 16.0,
+            targets: vec![(id(2), 1.0), (id(3), 1.0), (id(5), 1.0)],
+        };
+        let accs = accelerations(&pressure, &bodies);
+
+        // Pushed away from the source, a quarter as strong at twice the distance
+        assert!(accs[1].distance(Vec3A::X * 4.0) < 1e-6);
+        assert!(accs[2].distance(Vec3A::NEG_Z) < 1e-6);
+        assert_eq!(accs[0], Vec3A::ZERO);
+        assert_eq!(accs[3], Vec3A::ZERO);
+    }
+
+    #[test]
+    fn step_time_applies_forces() {
+        let bodies = [
+            SimulatedBody::test(1, 10.0, Vec3A::ZERO, Vec3A::ZERO),
+            SimulatedBody::test(2, 1.0, Vec3A::X * 10.0, Vec3A::Y),
+        ];
+        let thrust = ConstantThrust {
+            body: id(2),
+            force: Vec3A::Z * 3.0,
+        };
+
+        let step = |forces: &[&dyn ForceGenerator]| {
+            let mut bodies = bodies.clone();
+            GravityController::step_time(1.0, 0.5, 0.0, &mut bodies, forces);
+            bodies
+        };
+        let free = step(&[]);
+        let pushed = step(&[&thrust]);
+
+        assert_eq!(pushed[0].vel, free[0].vel);
+        assert!((pushed[1].vel - free[1].vel).distance(Vec3A::Z * 1.5) < 1e-6);
+    }
+}
@@ -0,0 +1,221 @@
+//! Reference frames for drawing predicted trajectories.
+//!
+//! Trajectories are simulated in the controller's inertial space, but are often easier to
+//! read relative to something moving: a moon's path around its planet is a clean ellipse
+//! relative to the planet, and horseshoe and tadpole orbits only show up in the frame
+//! co-rotating with a pair of bodies.
+//!
+//! A frame is anchored at its state at the start of the prediction, so trajectories start
+//! at the current positions of the bodies and line up with the scene.
+
+use super::controller::SimulatedBody;
+use glam::{Affine3A, Mat3A, Vec3A};
+use godot::prelude::*;
+use std::collections::HashMap;
+
+/// Frame that trajectories are drawn in, as selected in the editor.
+#[derive(GodotConvert, Var, Export, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[godot(via = i64)]
+pub enum TrajectoryFrame {
+    /// The controller's space
+    #[default]
+    Inertial,
+
+    /// Moving with the center body
+    BodyCentered,
+
+    /// Moving with the barycenter of the center body and the secondary body
+    Barycentric,
+
+    /// Moving with the barycenter of the center body and the secondary body, and rotating
+    /// so the line between them stays fixed
+    Synodic,
+
+    /// Each body relative to its own primary, bodies without a primary inertial
+    Auto,
+}
+
+/// Origin, and possibly rotation, of a frame, defined by the bodies it follows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FrameAnchor {
+    Inertial,
+    Body(InstanceId),
+    Barycenter(InstanceId, InstanceId),
+    Synodic(InstanceId, InstanceId),
+}
+
+impl FrameAnchor {
+    /// Resolves a frame selection to its anchor, falling back to simpler frames when
+    /// bodies are missing. `Auto` anchors each body separately, see [`FrameTracker::new`].
+    pub fn new(
+        frame: TrajectoryFrame,
+        center: Option<InstanceId>,
+        secondary: Option<InstanceId>,
+    ) -> Self {
+        let secondary = secondary.filter(|&s| Some(s) != center);
+        match (frame, center, secondary) {
+            (TrajectoryFrame::Inertial | TrajectoryFrame::Auto, _, _) | (_, None, _) => {
+                Self::Inertial
+            }
+            (TrajectoryFrame::Barycentric, Some(c), Some(s)) => Self::Barycenter(c, s),
+            (TrajectoryFrame::Synodic, Some(c), Some(s)) => Self::Synodic(c, s),
+            (_, Some(c), _) => Self::Body(c),
+        }
+    }
+
+    /// Returns the transform from frame to controller space.
+    ///
+    /// `None` if a body of the frame is missing, or the rotation of a synodic frame is
+    /// undefined because the pair isn't moving relative to each other.
+    fn frame_to_world(self, bodies: &HashMap<InstanceId, &SimulatedBody>) -> Option<Affine3A> {
+        let barycenter = |a: &SimulatedBody, b: &SimulatedBody| {
+            let total_mass = a.mass + b.mass;
+            if total_mass > 0.0 {
+                (a.pos * a.mass + b.pos * b.mass) / total_mass
+            } else {
+                (a.pos + b.pos) / 2.0
+            }
+        };
+
+        match self {
+            Self::Inertial => Some(Affine3A::IDENTITY),
+            Self::Body(id) => Some(Affine3A::from_translation(bodies.get(&id)?.pos.into())),
+            Self::Barycenter(a, b) => {
+                let origin = barycenter(bodies.get(&a)?, bodies.get(&b)?);
+                Some(Affine3A::from_translation(origin.into()))
+            }
+            Self::Synodic(a, b) => {
+                let (a, b) = (bodies.get(&a)?, bodies.get(&b)?);
+                let x_axis = (b.pos - a.pos).try_normalize()?;
+                let normal = (b.pos - a.pos).cross(b.vel - a.vel).try_normalize()?;
+
+                Some(Affine3A {
+                    matrix3: Mat3A::from_cols(x_axis, normal.cross(x_axis), normal),
+                    translation: barycenter(a, b),
+                })
+            }
+        }
+    }
+}
+
+/// Maps simulated positions into the frames of the bodies as the prediction advances.
+pub struct FrameTracker {
+    /// Anchor of each body, bodies without one use `default`
+    anchors: HashMap<InstanceId, FrameAnchor>,
+
+    /// Anchor of the test particles and bodies without their own
+    default: FrameAnchor,
+
+    /// Transform of each anchor at the start of the prediction
+    start: HashMap<FrameAnchor, Affine3A>,
+
+    /// Transform from controller space into the display space of each anchor at the
+    /// current step. Anchors whose bodies have merged keep their last transform
+    current: HashMap<FrameAnchor, Affine3A>,
+}
+
+impl FrameTracker {
+    /// Anchors the frames at the initial state of the bodies.
+    ///
+    /// # Parameters
+    /// - `default`: Anchor of all bodies without an entry in `anchors`
+    /// - `anchors`: Anchors of individual bodies, like their primaries in the `Auto` frame
+    pub fn new(
+        bodies: &[SimulatedBody],
+        default: FrameAnchor,
+        anchors: HashMap<InstanceId, FrameAnchor>,
+    ) -> Self {
+        let by_id = bodies
+            .iter()
+            .map(|b| (b.body_instance_id, b))
+            .collect::<HashMap<_, _>>();
+
+        let start = anchors
+            .values()
+            .copied()
+            .chain([default])
+            .filter_map(|anchor| Some((anchor, anchor.frame_to_world(&by_id)?)))
+            .collect::<HashMap<_, _>>();
+
+        Self {
+            anchors,
+            default,
+            current: start.keys().map(|&a| (a, Affine3A::IDENTITY)).collect(),
+            start,
+        }
+    }
+
+    /// Moves the frames along with the bodies after a simulation step.
+    pub fn update(&mut self, bodies: &[SimulatedBody]) {
+        let by_id = bodies
+            .iter()
+            .map(|b| (b.body_instance_id, b))
+            .collect::<HashMap<_, _>>();
+
+        for (anchor, start) in &self.start {
+            if let Some(transform) = anchor.frame_to_world(&by_id) {
+                self.current.insert(*anchor, *start * transform.inverse());
+            }
+        }
+    }
+
+    /// Maps the position of a body into its frame.
+    pub fn body_point(&self, body: InstanceId, pos: Vec3A) -> Vec3A {
+        let anchor = self.anchors.get(&body).unwrap_or(&self.default);
+        self.transform(anchor, pos)
+    }
+
+    /// Maps a position into the default frame.
+    pub fn point(&self, pos: Vec3A) -> Vec3A {
+        self.transform(&self.default, pos)
+    }
+
+    #[inline]
+    fn transform(&self, anchor: &FrameAnchor, pos: Vec3A) -> Vec3A {
+        match self.current.get(anchor) {
+            Some(transform) => transform.transform_point3a(pos),
+            None => pos,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn synodic_frame_holds_the_pair_still() {
+        let (a, b) = (InstanceId::from_i64(1), InstanceId::from_i64(2));
+        let anchor = FrameAnchor::new(TrajectoryFrame::Synodic, Some(a), Some(b));
+        assert_eq!(anchor, FrameAnchor::Synodic(a, b));
+
+        // Equal masses on a circle of radius 10 around the origin, rotating around +Y
+        let pair = |angle: f32| {
+            let dir = Vec3A::new(angle.cos(), 0.0, -angle.sin());
+            let vel = Vec3A::new(-angle.sin(), 0.0, -angle.cos());
+            [
+                SimulatedBody::test(1, 1.0, -dir * 10.0, -vel),
+                SimulatedBody::test(2, 1.0, dir * 10.0, vel),
+            ]
+        };
+
+        let start = pair(0.0);
+        let mut tracker = FrameTracker::new(&start, anchor, HashMap::new());
+
+        let moved = pair(1.0).map(|mut b| {
+            b.pos += Vec3A::new(5.0, 0.0, 0.0);
+            b
+        });
+        tracker.update(&moved);
+
+        for (before, after) in start.iter().zip(&moved) {
+            let point = tracker.body_point(after.body_instance_id, after.pos);
+            assert!(point.abs_diff_eq(before.pos, 1e-4), "{point}");
+        }
+
+        // Unrelated points rotate with the pair, like a third body at rest
+        let third = tracker.point(Vec3A::new(5.0, 0.0, 0.0) + Vec3A::X * 20.0);
+        let expected = Vec3A::new(1.0f32.cos(), 0.0, 1.0f32.sin()) * 20.0;
+        assert!(third.abs_diff_eq(expected, 1e-4), "{third}");
+    }
+}
@@ -1,7 +1,13 @@
-use super::{HasMass, HasPosition, HasVelocity, NBodyGravityCalculator};
+use super::{
+    HasMass, HasOblateness, HasPosition, HasVelocity, NBodyGravityCalculator,
+    field::{self, FieldQuantity},
+};
 use crate::{from_glam_vec3, octree::morton_based::MortonBasedOctree, to_glam_vec3};
 use glam::Vec3A;
-use godot::prelude::*;
+use godot::{
+    classes::{Image, ImageTexture3D},
+    prelude::*,
+};
 
 #[derive(GodotClass)]
 #[class(init, base = Node)]
@@ -57,6 +63,8 @@ impl HasVelocity for StarData {
     }
 }
 
+impl HasOblateness for StarData {}
+
 impl HasMass for StarData {
     #[inline(always)]
     fn get_mass(&self) -> f32 {
@@ -97,6 +105,52 @@ impl INode for GalaxyController {
     }
 }
 
+#[godot_api]
+impl GalaxyController {
+    /// Samples the gravity field of the stars on the parallelogram spanned by `axis_u` and
+    /// `axis_v` from `origin`, for gravity well overlays on the galaxy map.
+    ///
+    /// Returns null before the stars are loaded or for an empty resolution.
+    #[func]
+    fn sample_field_slice(
+        &self,
+        quantity: FieldQuantity,
+        origin: Vector3,
+        axis_u: Vector3,
+        axis_v: Vector3,
+        resolution: Vector2i,
+    ) -> Option<Gd<Image>> {
+        field::sample_slice(
+            self.grav_const,
+            self.stars.as_deref()?,
+            quantity,
+            origin,
+            axis_u,
+            axis_v,
+            resolution,
+        )
+    }
+
+    /// Samples the gravity field of the stars on a regular grid filling `bounds`.
+    ///
+    /// Returns null before the stars are loaded or for an empty resolution.
+    #[func]
+    fn sample_field_volume(
+        &self,
+        quantity: FieldQuantity,
+        bounds: Aabb,
+        resolution: Vector3i,
+    ) -> Option<Gd<ImageTexture3D>> {
+        field::sample_volume(
+            self.grav_const,
+            self.stars.as_deref()?,
+            quantity,
+            bounds,
+            resolution,
+        )
+    }
+}
+
 impl GalaxyController {
     fn get_stars(&mut self) {
         if !self.bridge_initialized {
@@ -0,0 +1,143 @@
+//! Zonal spherical harmonics of non-spherical bodies.
+//!
+//! Fast rotating planets bulge at their equator, which makes orbits around them precess.
+//! The bulge is described by the zonal harmonic coefficients `J2`, `J3` and `J4` of the
+//! gravitational potential
+//!
+//! `V = μ/r * (1 - Σ Jn * (R/r)^n * Pn(sin φ))`
+//!
+//! where `R` is the body's reference radius, `φ` the latitude above its equator and `Pn`
+//! the Legendre polynomials. `J2` dominates for most planets.
+
+use glam::Vec3A;
+
+/// Zonal harmonics of a body's gravity field.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ZonalHarmonics {
+    /// Reference radius the coefficients are normalized to
+    pub radius: f32,
+
+    /// Unit vector along the body's rotation axis
+    pub axis: Vec3A,
+
+    /// Coefficients `J2`, `J3` and `J4`
+    pub coefficients: [f32; 3],
+}
+
+impl ZonalHarmonics {
+    /// Acceleration on top of the point mass gravity at `rel_pos` relative to the body,
+    /// for a body with gravitational parameter `mu = G * M`.
+    ///
+    /// Zero inside the reference radius, where the expansion is not valid.
+    pub fn acceleration(&self, mu: f32, rel_pos: Vec3A) -> Vec3A {
+        let r_sq = rel_pos.length_squared();
+        if r_sq <= self.radius * self.radius {
+            return Vec3A::ZERO;
+        }
+
+        let r = r_sq.sqrt();
+        let r_hat = rel_pos / r;
+        let u = r_hat.dot(self.axis);
+        let u2 = u * u;
+
+        let [j2, j3, j4] = self.coefficients;
+        let ratio = self.radius / r;
+        let base = mu / r_sq;
+
+        // Each term is split into its components along r̂ and along the axis
+        let c2 = j2 * base * ratio * ratio;
+        let c3 = j3 * base * ratio * ratio * ratio;
+        let c4 = j4 * base * ratio * ratio * ratio * ratio;
+
+        let radial = -1.5 * c2 * (1.0 - 5.0 * u2)
+            + 2.5 * c3 * u * (7.0 * u2 - 3.0)
+            + 1.875 * c4 * (1.0 - 14.0 * u2 + 21.0 * u2 * u2);
+        let axial = -3.0 * c2 * u + 1.5 * c3 * (1.0 - 5.0 * u2) + 2.5 * c4 * u * (3.0 - 7.0 * u2);
+
+        r_hat * radial + self.axis * axial
+    }
+
+    /// Potential on top of the point mass potential at `rel_pos` relative to the body,
+    /// for a body with gravitational parameter `mu = G * M`.
+    ///
+    /// Follows the sign convention `a = -∇Φ`, and is zero inside the reference radius.
+    pub fn potential(&self, mu: f32, rel_pos: Vec3A) -> f32 {
+        let r_sq = rel_pos.length_squared();
+        if r_sq <= self.radius * self.radius {
+            return 0.0;
+        }
+
+        let r = r_sq.sqrt();
+        let u = rel_pos.dot(self.axis) / r;
+        let u2 = u * u;
+
+        let [j2, j3, j4] = self.coefficients;
+        let ratio = self.radius / r;
+
+        let p2 = (3.0 * u2 - 1.0) / 2.0;
+        let p3 = (5.0 * u2 - 3.0) * u / 2.0;
+        let p4 = (35.0 * u2 * u2 - 30.0 * u2 + 3.0) / 8.0;
+
+        mu / r * ratio * ratio * (j2 * p2 + ratio * (j3 * p3 + ratio * j4 * p4))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Perturbing part of the potential, whose gradient is the acceleration
+    fn potential(h: &ZonalHarmonics, mu: f32, rel_pos: Vec3A) -> f64 {
+        let r = f64::from(rel_pos.length());
+        let u = f64::from(rel_pos.normalize().dot(h.axis));
+        let ratio = f64::from(h.radius) / r;
+        let [j2, j3, j4] = h.coefficients.map(f64::from);
+
+        let p2 = (3.0 * u * u - 1.0) / 2.0;
+        let p3 = (5.0 * u.powi(3) - 3.0 * u) / 2.0;
+        let p4 = (35.0 * u.powi(4) - 30.0 * u * u + 3.0) / 8.0;
+
+        -f64::from(mu) / r
+            * (j2 * ratio.powi(2) * p2 + j3 * ratio.powi(3) * p3 + j4 * ratio.powi(4) * p4)
+    }
+
+    #[test]
+    fn acceleration_is_gradient_of_potential() {
+        let mu = 100.0;
+        let h = ZonalHarmonics {
+            radius: 2.0,
+            axis: Vec3A::new(0.2, 1.0, 0.1).normalize(),
+            coefficients: [0.1, -0.05, 0.03],
+        };
+
+        for pos in [
+            Vec3A::new(3.0, 1.0, 0.5),
+            Vec3A::new(-1.0, 4.0, 2.0),
+            Vec3A::new(0.5, -2.5, -3.0),
+        ] {
+            let eps = 1e-3;
+            let gradient = Vec3A::from_array([Vec3A::X, Vec3A::Y, Vec3A::Z].map(|dir| {
+                ((potential(&h, mu, pos + dir * eps) - potential(&h, mu, pos - dir * eps))
+                    / (2.0 * f64::from(eps))) as f32
+            }));
+
+            let acc = h.acceleration(mu, pos);
+            assert!(acc.abs_diff_eq(gradient, 1e-3), "{acc} vs {gradient}");
+            assert!((h.potential(mu, pos) as f64 + potential(&h, mu, pos)).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn j2_pulls_towards_equator() {
+        let h = ZonalHarmonics {
+            radius: 1.0,
+            axis: Vec3A::Y,
+            coefficients: [0.1, 0.0, 0.0],
+        };
+
+        // Above the equator the extra pull points down, towards the equatorial plane
+        assert!(h.acceleration(1.0, Vec3A::new(2.0, 1.0, 0.0)).y < 0.0);
+        // On the equator it pulls inwards
+        assert!(h.acceleration(1.0, Vec3A::new(2.0, 0.0, 0.0)).x < 0.0);
+    }
+}
@@ -0,0 +1,322 @@
+//! Who orbits whom.
+//!
+//! Every body orbits the smallest sphere of a heavier body it is inside of, its primary.
+//! Two spheres can decide the primary:
+//!
+//! - The Hill sphere, where a body's gravity dominates over the tides of its own primary,
+//!   with a radius of `d * cbrt(m / 3M)` for a body of mass `m` at distance `d` from its
+//!   primary of mass `M`.
+//! - The Laplace sphere of influence, where orbits are better approximated around the body
+//!   than around its primary, with a radius of `d * (m / M)^(2/5)`.
+//!
+//! The most massive body has no primary and an infinite sphere. The primaries form the
+//! [`OrbitalHierarchy`], a tree of stars, planets and moons.
+
+use super::{HasMass, HasPosition, HasVelocity, controller::SimulatedBody, orbit};
+use glam::Vec3A;
+use godot::prelude::*;
+use std::collections::HashMap;
+
+/// The sphere deciding the primary of a body, as selected in the editor.
+#[derive(GodotConvert, Var, Export, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[godot(via = i64)]
+pub enum InfluenceSphere {
+    /// Radius `d * cbrt(m / 3M)`
+    #[default]
+    Hill,
+
+    /// Radius `d * (m / M)^(2/5)`
+    SphereOfInfluence,
+}
+
+/// A body in the [`OrbitalHierarchy`].
+#[derive(Clone, Debug)]
+pub struct HierarchyNode {
+    pub body: InstanceId,
+
+    /// Index of the primary, `None` for the roots of the hierarchy
+    pub parent: Option<usize>,
+
+    /// The primary, which stays valid when the bodies are reordered
+    pub parent_body: Option<InstanceId>,
+
+    /// Indices of the bodies orbiting this body, in ascending order
+    pub children: Vec<usize>,
+
+    /// Number of primaries above the body
+    pub depth: usize,
+
+    /// Hill radius around the body, infinite for roots
+    pub hill_radius: f32,
+
+    /// Radius of the Laplace sphere of influence around the body, infinite for roots
+    pub sphere_of_influence: f32,
+
+    /// Whether the body is on a closed orbit around its primary.
+    /// Roots are never bound.
+    pub bound: bool,
+}
+
+/// The tree of primaries of the simulated bodies, indexed like the bodies.
+///
+/// [`update`](Self::update) rebuilds the whole tree, taking `O(N²)` time in the worst
+/// case, and compares it with the previous one, so changes of primary are found even when
+/// bodies were added, removed or merged. The [`GravityController`] throttles updates with
+/// its `hierarchy_update_interval`.
+///
+/// [`GravityController`]: super::controller::GravityController
+#[derive(Clone, Debug, Default)]
+pub struct OrbitalHierarchy {
+    pub nodes: Vec<HierarchyNode>,
+}
+
+impl OrbitalHierarchy {
+    /// Builds the hierarchy of `bodies` from scratch.
+    pub fn new(grav_const: f32, sphere: InfluenceSphere, bodies: &[SimulatedBody]) -> Self {
+        let mut hierarchy = Self::default();
+        hierarchy.update(grav_const, sphere, bodies);
+        hierarchy
+    }
+
+    /// Returns the primary of every body.
+    pub fn parents(&self) -> Vec<Option<usize>> {
+        self.nodes.iter().map(|n| n.parent).collect()
+    }
+
+    /// Returns the primary of `body`, expected at index `i`.
+    ///
+    /// `None` for roots, and when the hierarchy is out of date and has another body there.
+    pub fn primary_of(&self, i: usize, body: InstanceId) -> Option<InstanceId> {
+        self.nodes
+            .get(i)
+            .filter(|node| node.body == body)
+            .and_then(|node| node.parent_body)
+    }
+
+    /// Returns the bodies without a primary.
+    pub fn roots(&self) -> impl Iterator<Item = usize> + '_ {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| n.parent.is_none())
+            .map(|(i, _)| i)
+    }
+
+    /// Updates the hierarchy to the current state of `bodies`.
+    ///
+    /// # Returns
+    ///
+    /// Indices of the bodies whose primary changed since the last update. Bodies that
+    /// weren't part of the last update are not included.
+    pub fn update(
+        &mut self,
+        grav_const: f32,
+        sphere: InfluenceSphere,
+        bodies: &[SimulatedBody],
+    ) -> Vec<usize> {
+        let previous = self
+            .nodes
+            .iter()
+            .map(|n| (n.body, n.parent_body))
+            .collect::<HashMap<_, _>>();
+
+        let order = mass_order(bodies);
+
+        let mut nodes = bodies
+            .iter()
+            .map(|b| HierarchyNode {
+                body: b.body_instance_id,
+                parent: None,
+                parent_body: None,
+                children: Vec::new(),
+                depth: 0,
+                hill_radius: f32::INFINITY,
+                sphere_of_influence: f32::INFINITY,
+                bound: false,
+            })
+            .collect::<Vec<_>>();
+        let mut radii = vec![f32::INFINITY; bodies.len()];
+
+        for (k, &i) in order.iter().enumerate() {
+            let pos = bodies[i].get_pos();
+            let contains = |j: usize| {
+                bodies[j].get_mass() > 0.0 && bodies[j].get_pos().distance(pos) < radii[j]
+            };
+
+            let parent = order[..k]
+                .iter()
+                .copied()
+                .filter(|&j| contains(j))
+                .min_by(|&a, &b| radii[a].total_cmp(&radii[b]));
+
+            if let Some(p) = parent {
+                let rel_pos = pos - bodies[p].get_pos();
+                let rel_vel = bodies[i].get_vel() - bodies[p].get_vel();
+                let dist = rel_pos.length();
+                let mass_ratio = bodies[i].get_mass().max(0.0) / bodies[p].get_mass();
+                let mu = grav_const * (bodies[p].get_mass() + bodies[i].get_mass());
+
+                nodes[i].parent = Some(p);
+                nodes[i].parent_body = Some(bodies[p].body_instance_id);
+                nodes[i].depth = nodes[p].depth + 1;
+                nodes[i].hill_radius = dist * (mass_ratio / 3.0).cbrt();
+                nodes[i].sphere_of_influence = dist * mass_ratio.powf(0.4);
+                nodes[i].bound = 0.5 * rel_vel.length_squared() < mu / dist;
+            }
+
+            radii[i] = match sphere {
+                InfluenceSphere::Hill => nodes[i].hill_radius,
+                InfluenceSphere::SphereOfInfluence => nodes[i].sphere_of_influence,
+            };
+        }
+
+        for i in 0..nodes.len() {
+            if let Some(p) = nodes[i].parent {
+                nodes[p].children.push(i);
+            }
+        }
+
+        let changed = nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| {
+                previous
+                    .get(&node.body)
+                    .is_some_and(|&old| old != node.parent_body)
+            })
+            .map(|(i, _)| i)
+            .collect();
+
+        self.nodes = nodes;
+
+        changed
+    }
+}
+
+/// Indices of the bodies sorted by descending mass, lower indices first on ties.
+fn mass_order(bodies: &[SimulatedBody]) -> Vec<usize> {
+    let mut order = (0..bodies.len()).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| bodies[b].get_mass().total_cmp(&bodies[a].get_mass()));
+    order
+}
+
+/// Gives every dynamic or on-rails body the velocity of an orbit around its Hill sphere parent.
+///
+/// Each body is put on the periapsis of an orbit with the given eccentricity, or on a
+/// circular orbit for an eccentricity of 0, see [`orbit::periapsis_velocity`]. Parents are
+/// assigned before their children, so moons follow their planets.
+pub fn assign_orbital_velocities(grav_const: f32, eccentricity: f32, bodies: &mut [SimulatedBody]) {
+    let parents = OrbitalHierarchy::new(grav_const, InfluenceSphere::Hill, bodies).parents();
+
+    for i in mass_order(bodies) {
+        let Some(parent) = parents[i] else {
+            continue;
+        };
+        if !bodies[i].motion.has_free_velocity() {
+            continue;
+        }
+
+        let parent_vel = bodies[parent].get_vel();
+        let mu = grav_const * (bodies[parent].get_mass() + bodies[i].get_mass());
+        let rel_vel = orbit::periapsis_velocity(
+            mu,
+            bodies[i].get_pos() - bodies[parent].get_pos(),
+            bodies[i].get_vel() - parent_vel,
+            eccentricity,
+        );
+
+        bodies[i].set_vel(parent_vel + rel_vel);
+    }
+}
+
+/// Shifts the velocities of all dynamic and on-rails bodies so that the total momentum is zero.
+///
+/// Pinned and kinematic bodies keep their velocity, but their momentum is included.
+pub fn remove_net_momentum(bodies: &mut [SimulatedBody]) {
+    let free_mass: f32 = bodies
+        .iter()
+        .filter(|b| b.motion.has_free_velocity())
+        .map(HasMass::get_mass)
+        .sum();
+
+    if free_mass <= 0.0 {
+        return;
+    }
+
+    let momentum: Vec3A = bodies.iter().map(|b| b.get_mass() * b.get_vel()).sum();
+    let correction = momentum / free_mass;
+
+    bodies
+        .iter_mut()
+        .filter(|b| b.motion.has_free_velocity())
+        .for_each(|b| b.vel -= correction);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moons_orbit_their_planets() {
+        let mut bodies = vec![
+            SimulatedBody::test(1, 1.0, Vec3A::new(101.0, 0.0, 0.0), Vec3A::ZERO), // Moon
+            SimulatedBody::test(2, 1000.0, Vec3A::ZERO, Vec3A::ZERO),              // Star
+            SimulatedBody::test(3, 10.0, Vec3A::new(100.0, 0.0, 0.0), Vec3A::ZERO), // Planet
+            SimulatedBody::test(4, 10.0, Vec3A::new(0.0, 0.0, -50.0), Vec3A::ZERO), // Other planet
+        ];
+
+        let hierarchy = OrbitalHierarchy::new(1.0, InfluenceSphere::Hill, &bodies);
+        assert_eq!(hierarchy.parents(), vec![Some(2), None, Some(1), Some(1)]);
+        assert_eq!(hierarchy.nodes[1].children, vec![2, 3]);
+        assert_eq!(hierarchy.nodes[0].depth, 2);
+
+        assign_orbital_velocities(1.0, 0.0, &mut bodies);
+        remove_net_momentum(&mut bodies);
+
+        let momentum: Vec3A = bodies.iter().map(|b| b.mass * b.vel).sum();
+        assert!(momentum.abs_diff_eq(Vec3A::ZERO, 1e-3));
+
+        // The moon circles the planet at the speed of a circular orbit around it
+        let moon_speed = (bodies[0].vel - bodies[2].vel).length();
+        assert!((moon_speed - 11.0f32.sqrt()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn updates_follow_escaping_moons() {
+        let mut bodies = vec![
+            SimulatedBody::test(1, 1000.0, Vec3A::ZERO, Vec3A::ZERO),
+            SimulatedBody::test(2, 10.0, Vec3A::new(100.0, 0.0, 0.0), Vec3A::ZERO),
+            SimulatedBody::test(3, 1.0, Vec3A::new(101.0, 0.0, 0.0), Vec3A::ZERO),
+        ];
+        bodies[2].vel = Vec3A::new(0.0, 0.0, 3.0);
+
+        let mut hierarchy = OrbitalHierarchy::new(1.0, InfluenceSphere::Hill, &bodies);
+        assert_eq!(hierarchy.nodes[2].parent, Some(1));
+        assert!(hierarchy.nodes[2].bound);
+
+        // Still inside the Hill sphere, but too fast to stay
+        bodies[2].vel = Vec3A::new(0.0, 0.0, 10.0);
+        assert!(
+            hierarchy
+                .update(1.0, InfluenceSphere::Hill, &bodies)
+                .is_empty()
+        );
+        assert!(!hierarchy.nodes[2].bound);
+
+        // Out of the Hill sphere of the planet, so it orbits the star
+        bodies[2].pos = Vec3A::new(120.0, 0.0, 0.0);
+        let changed = hierarchy.update(1.0, InfluenceSphere::Hill, &bodies);
+        assert_eq!(changed, vec![2]);
+        assert_eq!(hierarchy.nodes[2].parent, Some(0));
+        assert!(hierarchy.nodes[1].children.is_empty());
+
+        // Back around the planet, then the planet falls into the star
+        bodies[2].pos = Vec3A::new(101.0, 0.0, 0.0);
+        hierarchy.update(1.0, InfluenceSphere::Hill, &bodies);
+        bodies.remove(1);
+        bodies[0].mass += 10.0;
+        let changed = hierarchy.update(1.0, InfluenceSphere::Hill, &bodies);
+        assert_eq!(changed, vec![1]);
+        assert_eq!(hierarchy.nodes[1].parent, Some(0));
+    }
+}
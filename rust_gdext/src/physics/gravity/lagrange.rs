@@ -0,0 +1,221 @@
+//! Lagrange points of a primary and a secondary body.
+//!
+//! In the circular restricted three-body problem, a massless body co-rotating with two
+//! bodies can stay still relative to them at five points. L1, L2 and L3 lie on the line
+//! through both bodies: between them, beyond the secondary and opposite of it. L4 and L5
+//! form equilateral triangles with them, leading and trailing the secondary.
+//!
+//! The approximation ignores every other body and assumes a circular orbit.
+//! [`refine_lagrange_points`] corrects it against the gravity field of all bodies.
+
+use super::{NBodyGravityCalculator, controller::SimulatedBody, direct_summation::DirectSummation};
+use glam::{Mat3A, Vec3A};
+
+/// Maximum number of Newton iterations when solving for a point
+const MAX_ITERATIONS: usize = 20;
+
+/// Relative tolerance of the collinear points, in units of the separation
+const TOLERANCE: f64 = 1e-12;
+
+/// Relative tolerance of the refined points, in units of the separation
+const REFINE_TOLERANCE: f32 = 1e-6;
+
+/// Step of the numeric Jacobian, in units of the separation
+const JACOBIAN_STEP: f32 = 1e-3;
+
+/// Refined points moving further than this from their approximation, in units of the
+/// separation, have converged to something else and are discarded
+const MAX_REFINEMENT: f32 = 0.25;
+
+/// Computes L1 to L5 of `secondary` orbiting `primary` in the restricted three-body problem.
+///
+/// The orbital plane is spanned by the separation and the relative velocity. Without a
+/// relative velocity, L4 and L5 are placed in an arbitrary plane containing both bodies.
+///
+/// # Returns
+///
+/// The points in order from L1 to L5, or `None` when the bodies have no mass or overlap.
+pub fn lagrange_points(primary: &SimulatedBody, secondary: &SimulatedBody) -> Option<[Vec3A; 5]> {
+    let total_mass = primary.mass + secondary.mass;
+    let separation = secondary.pos - primary.pos;
+    let distance = separation.length();
+
+    if primary.mass <= 0.0 || secondary.mass < 0.0 || distance <= 0.0 {
+        return None;
+    }
+
+    let mu = f64::from(secondary.mass / total_mass);
+    let barycenter = primary.pos + separation * (secondary.mass / total_mass);
+
+    let x_axis = separation / distance;
+    let normal = separation.cross(secondary.vel - primary.vel);
+    let normal = if normal.length_squared() > 0.0 {
+        normal.normalize()
+    } else {
+        x_axis.any_orthonormal_vector()
+    };
+    // Direction of motion of the secondary on a prograde orbit
+    let y_axis = normal.cross(x_axis);
+
+    // Initial guesses from the Hill radius
+    let hill = (mu / 3.0).cbrt();
+    let collinear = [1.0 - mu - hill, 1.0 - mu + hill, -1.0 - 5.0 * mu / 12.0]
+        .map(|x| barycenter + x_axis * (solve_collinear(mu, x) as f32 * distance));
+
+    let triangle_x = (0.5 - mu) as f32 * distance;
+    let triangle_y = 3.0f32.sqrt() / 2.0 * distance;
+
+    Some([
+        collinear[0],
+        collinear[1],
+        collinear[2],
+        barycenter + x_axis * triangle_x + y_axis * triangle_y,
+        barycenter + x_axis * triangle_x - y_axis * triangle_y,
+    ])
+}
+
+/// Solves for a collinear point with Newton's method, in units of the separation with
+/// the barycenter at the origin, the primary at `-mu` and the secondary at `1 - mu`.
+fn solve_collinear(mu: f64, mut x: f64) -> f64 {
+    for _ in 0..MAX_ITERATIONS {
+        let (d1, d2) = (x + mu, x - 1.0 + mu);
+        let (r1, r2) = (d1.abs(), d2.abs());
+        if r1 == 0.0 || r2 == 0.0 {
+            break;
+        }
+
+        let f = x - (1.0 - mu) * d1 / r1.powi(3) - mu * d2 / r2.powi(3);
+        let df = 1.0 + 2.0 * (1.0 - mu) / r1.powi(3) + 2.0 * mu / r2.powi(3);
+        let step = f / df;
+
+        x -= step;
+        if step.abs() < TOLERANCE {
+            break;
+        }
+    }
+
+    x
+}
+
+/// Moves the Lagrange points of a pair onto the equilibria of the full gravity field.
+///
+/// Solves `a(x) - a_c = ω × (ω × (x - c))` with Newton's method, where `a` is the
+/// acceleration from all bodies, `c` and `a_c` are the barycenter of the pair and its
+/// acceleration, and `ω` is the angular velocity of the pair. Points that don't converge
+/// near their approximation are left unchanged.
+///
+/// # Parameters
+/// - `grav_const`: The gravitational constant
+/// - `bodies`: All bodies attracting the points
+/// - `primary`, `secondary`: Indices of the pair in `bodies`
+/// - `points`: Points to refine in-place, usually from [`lagrange_points`]
+pub fn refine_lagrange_points(
+    grav_const: f32,
+    bodies: &[SimulatedBody],
+    primary: usize,
+    secondary: usize,
+    points: &mut [Vec3A],
+) {
+    let (p, s) = (&bodies[primary], &bodies[secondary]);
+    let total_mass = p.mass + s.mass;
+    let separation = s.pos - p.pos;
+    let distance_sq = separation.length_squared();
+    if total_mass <= 0.0 || distance_sq <= 0.0 {
+        return;
+    }
+
+    let calculator = DirectSummation::new(bodies);
+    let accelerations = |points: &[Vec3A]| calculator.calc_accs_at::<false>(grav_const, points);
+
+    let barycenter = (p.pos * p.mass + s.pos * s.mass) / total_mass;
+    let omega = separation.cross(s.vel - p.vel) / distance_sq;
+    let [acc_p, acc_s] = accelerations(&[p.pos, s.pos])[..] else {
+        unreachable!()
+    };
+    let acc_barycenter = (acc_p * p.mass + acc_s * s.mass) / total_mass;
+
+    let residual =
+        |x: Vec3A, acc: Vec3A| acc - acc_barycenter - omega.cross(omega.cross(x - barycenter));
+
+    let distance = distance_sq.sqrt();
+    let h = JACOBIAN_STEP * distance;
+
+    for point in points {
+        let mut x = *point;
+
+        for _ in 0..MAX_ITERATIONS {
+            let samples = [
+                x,
+                x + Vec3A::X * h,
+                x - Vec3A::X * h,
+                x + Vec3A::Y * h,
+                x - Vec3A::Y * h,
+                x + Vec3A::Z * h,
+                x - Vec3A::Z * h,
+            ];
+            let f = samples
+                .iter()
+                .zip(accelerations(&samples))
+                .map(|(&x, acc)| residual(x, acc))
+                .collect::<Vec<_>>();
+
+            let jacobian = Mat3A::from_cols(
+                (f[1] - f[2]) / (2.0 * h),
+                (f[3] - f[4]) / (2.0 * h),
+                (f[5] - f[6]) / (2.0 * h),
+            );
+            if jacobian.determinant() == 0.0 {
+                break;
+            }
+
+            let step = jacobian.inverse() * -f[0];
+            x += step;
+            if step.length() < REFINE_TOLERANCE * distance || !x.is_finite() {
+                break;
+            }
+        }
+
+        if x.is_finite() && x.distance(*point) < MAX_REFINEMENT * distance {
+            *point = x;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn approximation_is_an_equilibrium_of_the_pair() {
+        // Circular orbit around the barycenter, moving along +Z
+        let (m1, m2, distance) = (1000.0, 10.0, 100.0);
+        let speed = f32::sqrt((m1 + m2) / distance);
+        let bodies = [
+            SimulatedBody::test(1, m1, Vec3A::ZERO, Vec3A::ZERO),
+            SimulatedBody::test(2, m2, Vec3A::X * distance, Vec3A::Z * speed),
+        ];
+
+        let points = lagrange_points(&bodies[0], &bodies[1]).unwrap();
+
+        // L1 and L2 lie about a Hill radius from the secondary, L3 opposite of it
+        let hill = distance * (m2 / (3.0 * m1)).cbrt();
+        assert!((points[0].x - (distance - hill)).abs() < 0.1 * hill);
+        assert!((points[1].x - (distance + hill)).abs() < 0.1 * hill);
+        assert!((points[2].x + distance).abs() < 0.01 * distance);
+
+        // L4 leads the secondary, L5 trails it
+        assert!(points[3].z > 0.0 && points[4].z < 0.0);
+        for l in &points[3..] {
+            assert!((l.distance(bodies[0].pos) - distance).abs() < 1e-3);
+            assert!((l.distance(bodies[1].pos) - distance).abs() < 1e-3);
+        }
+
+        // The two-body field is the restricted three-body problem, so refining barely moves
+        // the points
+        let mut refined = points;
+        refine_lagrange_points(1.0, &bodies, 0, 1, &mut refined);
+        for (l, r) in points.iter().zip(&refined) {
+            assert!(l.distance(*r) < 1e-3 * distance, "{l} vs {r}");
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//! Markers showing the Lagrange points of a pair of bodies.
+
+use super::{body::GravityBody, controller::GravityController};
+use crate::from_glam_vec3;
+use godot::{
+    classes::{
+        ImmediateMesh, Mesh, MeshInstance3D, StandardMaterial3D,
+        base_material_3d::{Flags, ShadingMode},
+        mesh::PrimitiveType,
+    },
+    prelude::*,
+};
+
+/// Draws a cross at each Lagrange point of `secondary` orbiting `primary`.
+///
+/// The markers follow the pair every frame, in the editor and in game. The points are
+/// drawn in global coordinates, regardless of where this node is placed.
+#[derive(GodotClass)]
+#[class(tool, init, base = Node3D)]
+pub struct LagrangeMarkers {
+    base: Base<Node3D>,
+
+    /// Controller managing both bodies
+    #[export]
+    pub controller: Option<Gd<GravityController>>,
+
+    /// The heavier body of the pair
+    #[export]
+    pub primary: Option<Gd<GravityBody>>,
+
+    /// The body orbiting the primary
+    #[export]
+    pub secondary: Option<Gd<GravityBody>>,
+
+    /// Whether to correct the points against the gravity of all bodies,
+    /// which costs a few hundred gravity evaluations per frame
+    #[export]
+    #[init(val = true)]
+    pub refine: bool,
+
+    /// Half the length of each arm of a marker
+    #[export]
+    #[init(val = 1.0)]
+    pub marker_size: f32,
+
+    #[export]
+    #[init(val = Color::from_rgb(0.4, 1.0, 0.6))]
+    pub marker_color: Color,
+
+    mesh_instance: Option<Gd<MeshInstance3D>>,
+}
+
+#[godot_api]
+impl INode3D for LagrangeMarkers {
+    fn ready(&mut self) {
+        let mut material = StandardMaterial3D::new_gd();
+        material.set_shading_mode(ShadingMode::UNSHADED);
+        material.set_flag(Flags::ALBEDO_FROM_VERTEX_COLOR, true);
+        material.set_flag(Flags::DISABLE_FOG, true);
+
+        let mut mesh_instance = MeshInstance3D::new_alloc();
+        mesh_instance.set_material_override(&material);
+        // Points are in global coordinates
+        mesh_instance.set_as_top_level(true);
+
+        self.base_mut().add_child(&mesh_instance);
+        self.mesh_instance = Some(mesh_instance);
+    }
+
+    fn process(&mut self, _delta: f64) {
+        self.update_markers();
+    }
+}
+
+#[godot_api]
+impl LagrangeMarkers {
+    /// Redraws the markers at the current Lagrange points.
+    #[func]
+    fn update_markers(&mut self) {
+        let Some(mesh_instance) = self.mesh_instance.as_mut() else {
+            return;
+        };
+
+        let points = match (&self.controller, &self.primary, &self.secondary) {
+            (Some(controller), Some(primary), Some(secondary)) => controller
+                .bind()
+                .lagrange_points(primary, secondary, self.refine),
+            _ => None,
+        };
+        let Some(points) = points else {
+            mesh_instance.set_mesh(Gd::<Mesh>::null_arg());
+            return;
+        };
+
+        let mut mesh = ImmediateMesh::new_gd();
+        mesh.surface_begin(PrimitiveType::LINES);
+
+        for point in points.map(from_glam_vec3) {
+            for axis in [Vector3::RIGHT, Vector3::UP, Vector3::BACK] {
+                mesh.surface_set_color(self.marker_color);
+                mesh.surface_add_vertex(point - axis * self.marker_size);
+                mesh.surface_set_color(self.marker_color);
+                mesh.surface_add_vertex(point + axis * self.marker_size);
+            }
+        }
+
+        mesh.surface_end();
+        mesh_instance.set_mesh(&mesh);
+    }
+}
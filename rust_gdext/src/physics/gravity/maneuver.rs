@@ -0,0 +1,144 @@
+//! Planned impulsive velocity changes of bodies.
+//!
+//! A maneuver changes the velocity of a body instantly at a given simulation time, like a
+//! short engine burn. The velocity change can be given in the controller's space, or in
+//! the orbital frame of the body around its primary: along its direction of motion
+//! (prograde), perpendicular to its orbital plane (normal) and away from the primary
+//! (radial). Trajectory predictions apply the maneuvers at the step they fall into and
+//! color the path after each one differently.
+
+use super::controller::SimulatedBody;
+use glam::{Mat3A, Vec3A};
+use godot::prelude::*;
+
+/// Coordinates of the velocity change of a maneuver, as selected in the editor.
+#[derive(GodotConvert, Var, Export, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[godot(via = i64)]
+pub enum ManeuverFrame {
+    /// X, Y and Z in the controller's space
+    #[default]
+    Inertial,
+
+    /// Prograde, normal and radial relative to the primary, at the time of the maneuver
+    Orbital,
+}
+
+/// A velocity change of a body at a given time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Maneuver {
+    /// Simulation time at which the maneuver is executed
+    pub time: f64,
+
+    /// Velocity change, in the coordinates of `frame`
+    pub delta_v: Vec3A,
+
+    pub frame: ManeuverFrame,
+
+    /// Color of the predicted path after the maneuver
+    pub color: Color,
+}
+
+impl Maneuver {
+    /// Converts the velocity change into the controller's space.
+    ///
+    /// # Parameters
+    /// - `rel_pos`, `rel_vel`: Position and velocity of the body relative to its primary,
+    ///   only used by the orbital frame
+    ///
+    /// Without a relative velocity the orbital frame is undefined, and the velocity change
+    /// is applied as if it was inertial.
+    pub fn world_delta_v(&self, rel_pos: Vec3A, rel_vel: Vec3A) -> Vec3A {
+        match self.frame {
+            ManeuverFrame::Inertial => self.delta_v,
+            ManeuverFrame::Orbital => match orbital_basis(rel_pos, rel_vel) {
+                Some(basis) => basis * self.delta_v,
+                None => self.delta_v,
+            },
+        }
+    }
+}
+
+/// Returns the prograde, normal and radial directions as the columns of a matrix.
+///
+/// The radial direction points away from the primary, perpendicular to the prograde
+/// direction, so it's exact for circular orbits only. For radial motion, where the
+/// orbital plane is undefined, an arbitrary normal is chosen.
+fn orbital_basis(rel_pos: Vec3A, rel_vel: Vec3A) -> Option<Mat3A> {
+    let prograde = rel_vel.try_normalize()?;
+    let normal = rel_pos
+        .cross(rel_vel)
+        .try_normalize()
+        .unwrap_or_else(|| prograde.any_orthonormal_vector());
+    let radial = prograde.cross(normal);
+
+    Some(Mat3A::from_cols(prograde, normal, radial))
+}
+
+/// A maneuver of a simulated body, with the primary its orbital frame is relative to.
+#[derive(Clone, Copy, Debug)]
+pub struct PlannedManeuver {
+    pub body: InstanceId,
+
+    /// Primary of the body when the maneuver was planned, `None` to use the origin
+    pub primary: Option<InstanceId>,
+
+    pub maneuver: Maneuver,
+}
+
+impl PlannedManeuver {
+    /// Changes the velocity of the body in `bodies`.
+    ///
+    /// # Returns
+    ///
+    /// Whether the maneuver was applied. Bodies that have merged or whose velocity is
+    /// fixed by their motion are left unchanged.
+    pub fn apply(&self, bodies: &mut [SimulatedBody]) -> bool {
+        let Some(i) = bodies.iter().position(|b| b.body_instance_id == self.body) else {
+            return false;
+        };
+        if !bodies[i].motion.has_free_velocity() {
+            return false;
+        }
+
+        let (origin, origin_vel) = self
+            .primary
+            .and_then(|id| bodies.iter().find(|b| b.body_instance_id == id))
+            .map_or((Vec3A::ZERO, Vec3A::ZERO), |p| (p.pos, p.vel));
+
+        let body = &mut bodies[i];
+        body.vel += self
+            .maneuver
+            .world_delta_v(body.pos - origin, body.vel - origin_vel);
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orbital_frame_follows_the_orbit() {
+        // Circular orbit in the XZ plane, moving along +Z
+        let (rel_pos, rel_vel) = (Vec3A::X * 10.0, Vec3A::Z * 2.0);
+        let maneuver = |delta_v| Maneuver {
+            time: 0.0,
+            delta_v,
+            frame: ManeuverFrame::Orbital,
+            color: Color::WHITE,
+        };
+
+        let prograde = maneuver(Vec3A::X).world_delta_v(rel_pos, rel_vel);
+        let normal = maneuver(Vec3A::Y).world_delta_v(rel_pos, rel_vel);
+        let radial = maneuver(Vec3A::Z).world_delta_v(rel_pos, rel_vel);
+
+        assert!(prograde.abs_diff_eq(Vec3A::Z, 1e-6), "{prograde}");
+        assert!(normal.abs_diff_eq(rel_pos.cross(rel_vel).normalize(), 1e-6));
+        assert!(radial.abs_diff_eq(Vec3A::X, 1e-6), "{radial}");
+
+        // Without relative motion there is no frame
+        let still = maneuver(Vec3A::X).world_delta_v(rel_pos, Vec3A::ZERO);
+        assert_eq!(still, Vec3A::X);
+    }
+}
@@ -5,20 +5,46 @@
 
 pub mod barnes_hut;
 pub mod body;
+pub mod broadphase;
+pub mod collision;
 pub mod controller;
+pub mod decimation;
 pub mod direct_summation;
+pub mod encounter;
+pub mod field;
+pub mod forces;
+pub mod frame;
 pub mod galaxy_controller;
-pub mod trajectories;
+pub mod harmonics;
+pub mod hierarchy;
+pub mod lagrange;
+pub mod lagrange_markers;
+pub mod maneuver;
+pub mod motion;
+pub mod orbit;
+pub mod receiver;
+pub mod rotation;
+pub mod test_particles;
+pub mod tidal;
 
 use glam::Vec3A;
+use harmonics::ZonalHarmonics;
 
 /// Common softening factor for gravitational calculations to prevent singularities.
 /// This value is squared to avoid square roots in distance comparisons.
 const GRAVITATIONAL_SOFTENING: f32 = 1e-2;
 pub const GRAVITATIONAL_SOFTENING_SQUARED: f32 = GRAVITATIONAL_SOFTENING * GRAVITATIONAL_SOFTENING;
 
-pub fn merge_radius(scaler: f32, m1: f32, m2: f32) -> f32 {
-    scaler * (m1.log10() + m2.log10()).max(0.0) / 2.0
+/// Mass-based fallback radius for bodies without a physical radius.
+///
+/// Two bodies with heuristic radii collide at `scaler * (log10(m1) + log10(m2)) / 2`.
+pub fn heuristic_radius(scaler: f32, mass: f32) -> f32 {
+    scaler * mass.log10().max(0.0) / 2.0
+}
+
+/// Radius of a uniform sphere with the given mass and density.
+pub fn radius_from_density(mass: f32, density: f32) -> f32 {
+    (3.0 * mass / (4.0 * std::f32::consts::PI * density)).cbrt()
 }
 
 pub trait HasPosition {
@@ -36,6 +62,19 @@ pub trait HasMass {
     fn set_mass(&mut self, mass: f32);
 }
 
+pub trait HasOblateness {
+    /// Zonal harmonics of the body's gravity field, or `None` for a point mass.
+    #[inline(always)]
+    fn get_zonal_harmonics(&self) -> Option<ZonalHarmonics> {
+        None
+    }
+}
+
+pub trait HasRadius {
+    fn get_radius(&self) -> Option<f32>;
+    fn set_radius(&mut self, radius: Option<f32>);
+}
+
 pub trait PosMass: HasPosition + HasMass {
     #[inline]
     fn weighted_pos(&self) -> Vec3A {
@@ -68,10 +107,32 @@ pub trait VelMass: HasVelocity + HasMass {
 
 impl<T: HasVelocity + HasMass> VelMass for T {}
 
+pub trait RadiusMass: HasRadius + HasMass {
+    /// Radius used for collision checks.
+    ///
+    /// Uses the physical radius when set, otherwise falls back to [`heuristic_radius`].
+    #[inline]
+    fn collision_radius(&self, merge_scaler: f32) -> f32 {
+        self.get_radius()
+            .unwrap_or_else(|| heuristic_radius(merge_scaler, self.get_mass()))
+    }
+}
+
+impl<T: HasRadius + HasMass> RadiusMass for T {}
+
 pub trait NBodyGravityCalculator<T>
 where
     T: PosMass,
 {
     fn calc_accs<const PARALLEL: bool>(&self, g: f32) -> Vec<Vec3A>;
-    fn detect_collisions(&self, merge_scaler: f32) -> Vec<(usize, usize)>;
+
+    /// Calculates the accelerations at arbitrary points caused by all particles.
+    ///
+    /// The points themselves have no mass and don't affect each other.
+    fn calc_accs_at<const PARALLEL: bool>(&self, g: f32, points: &[Vec3A]) -> Vec<Vec3A>;
+
+    /// Calculates the gravitational potentials at arbitrary points caused by all particles.
+    ///
+    /// Potentials are negative and follow the sign convention `a = -∇Φ`.
+    fn calc_potentials_at<const PARALLEL: bool>(&self, g: f32, points: &[Vec3A]) -> Vec<f32>;
 }
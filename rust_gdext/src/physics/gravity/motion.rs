@@ -0,0 +1,230 @@
+//! How the motion of a body is determined.
+//!
+//! By default bodies are integrated from the gravity acting on them, but designers can
+//! also pin bodies in place or move them along a scripted path. Pinned and kinematic bodies
+//! still attract every other body, which makes it easy to build stable showcase systems.
+//!
+//! Bodies on rails follow an analytic Kepler orbit around a parent body instead, see
+//! [`orbit`](super::orbit). Large systems can keep most bodies on rails and only integrate
+//! the ones close to the player.
+
+use super::controller::SimulatedBody;
+use glam::Vec3A;
+use godot::prelude::*;
+use std::{collections::HashMap, sync::Arc};
+
+/// Motion mode of a [`GravityBody`](super::body::GravityBody), as selected in the editor.
+#[derive(GodotConvert, Var, Export, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[godot(via = i64)]
+pub enum MotionMode {
+    /// Moved by the gravity and forces acting on it
+    #[default]
+    Dynamic,
+
+    /// Fixed in place, ignoring all forces
+    Pinned,
+
+    /// Moved along its kinematic path, or by an animation or script when it has none
+    Kinematic,
+
+    /// Moved along a Kepler orbit around its rails parent
+    OnRails,
+}
+
+/// Motion of a [`SimulatedBody`](super::controller::SimulatedBody).
+#[derive(Clone, Debug, Default)]
+pub enum BodyMotion {
+    /// Integrated from the accelerations acting on the body
+    #[default]
+    Dynamic,
+
+    /// Never moves
+    Pinned,
+
+    /// Follows the path if there is one. Otherwise the body is moved outside the
+    /// simulation and holds its position within a step.
+    Kinematic(Option<Arc<KinematicPath>>),
+
+    /// Follows a Kepler orbit around the body with the given instance ID, starting from
+    /// its current state relative to it. Falls back to integration when the parent is
+    /// missing or the parents form a cycle.
+    OnRails(InstanceId),
+}
+
+impl BodyMotion {
+    /// Whether the body is moved by the accelerations acting on it.
+    #[inline(always)]
+    pub fn is_dynamic(&self) -> bool {
+        matches!(self, Self::Dynamic)
+    }
+
+    /// Whether the velocity of the body can be chosen freely. Bodies on rails move along
+    /// the orbit following from their velocity, pinned and kinematic bodies ignore it.
+    #[inline(always)]
+    pub fn has_free_velocity(&self) -> bool {
+        matches!(self, Self::Dynamic | Self::OnRails(_))
+    }
+}
+
+/// Resolves the parents of the bodies on rails.
+///
+/// # Returns
+///
+/// Pairs of `(body, parent)` indices for every body on rails whose parent is simulated.
+/// Parents come before their children, so propagating the pairs in order always moves a
+/// parent before the bodies orbiting it. Bodies whose parent is missing, or whose parents
+/// form a cycle, are left out.
+pub fn rails_order(bodies: &[SimulatedBody]) -> Vec<(usize, usize)> {
+    if !bodies
+        .iter()
+        .any(|b| matches!(b.motion, BodyMotion::OnRails(_)))
+    {
+        return Vec::new();
+    }
+
+    let index_of = bodies
+        .iter()
+        .enumerate()
+        .map(|(i, b)| (b.body_instance_id, i))
+        .collect::<HashMap<_, _>>();
+
+    let parents = bodies
+        .iter()
+        .enumerate()
+        .map(|(i, b)| match b.motion {
+            BodyMotion::OnRails(parent) => index_of.get(&parent).copied().filter(|&p| p != i),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let mut depths = vec![RailsDepth::Unknown; bodies.len()];
+    for i in 0..bodies.len() {
+        rails_depth(i, &parents, &mut depths);
+    }
+
+    let mut order = parents
+        .iter()
+        .enumerate()
+        .filter_map(|(i, &parent)| {
+            let RailsDepth::Known(Some(depth @ 1..)) = depths[i] else {
+                return None;
+            };
+            Some((depth, i, parent?))
+        })
+        .collect::<Vec<_>>();
+    order.sort_unstable();
+
+    order
+        .into_iter()
+        .map(|(_, i, parent)| (i, parent))
+        .collect()
+}
+
+#[derive(Clone, Copy)]
+enum RailsDepth {
+    Unknown,
+    Visiting,
+    /// Number of rails parents above the body, `None` for bodies in or below a cycle
+    Known(Option<usize>),
+}
+
+/// Returns the number of rails parents above body `i`, memoized in `depths`.
+fn rails_depth(i: usize, parents: &[Option<usize>], depths: &mut [RailsDepth]) -> Option<usize> {
+    match depths[i] {
+        RailsDepth::Known(depth) => return depth,
+        RailsDepth::Visiting => return None,
+        RailsDepth::Unknown => {}
+    }
+
+    depths[i] = RailsDepth::Visiting;
+    let depth = match parents[i] {
+        Some(parent) => rails_depth(parent, parents, depths).map(|d| d + 1),
+        None => Some(0),
+    };
+    depths[i] = RailsDepth::Known(depth);
+
+    depth
+}
+
+/// A path traversed at constant speed over a fixed duration.
+#[derive(Clone, Debug)]
+pub struct KinematicPath {
+    /// Evenly spaced points along the path
+    pub points: Vec<Vec3A>,
+
+    /// Time to traverse the whole path
+    pub duration: f32,
+
+    /// Whether to restart at the first point after the last one,
+    /// instead of stopping there
+    pub looping: bool,
+}
+
+impl KinematicPath {
+    /// Returns the position on the path at simulation time `time`.
+    pub fn sample(&self, time: f64) -> Vec3A {
+        match self.points.as_slice() {
+            [] => Vec3A::ZERO,
+            [point] => *point,
+            points => {
+                let duration = f64::from(self.duration.max(f32::EPSILON));
+                let progress = if self.looping {
+                    time.rem_euclid(duration) / duration
+                } else {
+                    (time / duration).clamp(0.0, 1.0)
+                };
+
+                // The last point closes the loop for closed curves, so `progress == 1.0`
+                // lands on it exactly
+                let t = progress as f32 * (points.len() - 1) as f32;
+                let i = (t as usize).min(points.len() - 2);
+
+                points[i].lerp(points[i + 1], t - i as f32)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rails_parents_come_first() {
+        let on_rails = |parent| BodyMotion::OnRails(InstanceId::from_i64(parent));
+        let body = |id, motion| SimulatedBody {
+            motion,
+            ..SimulatedBody::test(id, 1.0, Vec3A::ZERO, Vec3A::ZERO)
+        };
+        let bodies = [
+            // Moon around a planet around a star
+            body(1, on_rails(2)),
+            body(2, on_rails(3)),
+            body(3, BodyMotion::Dynamic),
+            // Cycle and missing parent
+            body(4, on_rails(5)),
+            body(5, on_rails(4)),
+            body(6, on_rails(99)),
+        ];
+
+        assert_eq!(rails_order(&bodies), vec![(1, 2), (0, 1)]);
+    }
+
+    #[test]
+    fn path_sampling_wraps_and_clamps() {
+        let mut path = KinematicPath {
+            points: vec![Vec3A::ZERO, Vec3A::X, Vec3A::new(1.0, 1.0, 0.0)],
+            duration: 2.0,
+            looping: true,
+        };
+
+        assert_eq!(path.sample(0.5), Vec3A::new(0.5, 0.0, 0.0));
+        assert_eq!(path.sample(1.5), Vec3A::new(1.0, 0.5, 0.0));
+        assert_eq!(path.sample(2.5), path.sample(0.5));
+        assert_eq!(path.sample(-1.5), path.sample(0.5));
+
+        path.looping = false;
+        assert_eq!(path.sample(10.0), Vec3A::new(1.0, 1.0, 0.0));
+        assert_eq!(path.sample(-1.0), Vec3A::ZERO);
+    }
+}
@@ -0,0 +1,438 @@
+//! Analytic two-body orbital mechanics.
+//!
+//! Bodies on rails follow exact Kepler orbits around their parent instead of being
+//! integrated. All calculations run in `f64`, as the universal anomaly solver loses
+//! too much precision in `f32` for long or highly eccentric orbits.
+//!
+//! Orbits can also be described by their classical [`OrbitalElements`]. Godot is Y-up, so
+//! the reference plane is the XZ plane with the reference direction along +X, and prograde
+//! orbits turn counter-clockwise when seen from above.
+
+use glam::{DQuat, DVec3, Vec3A};
+use std::f64::consts::TAU;
+
+/// Maximum number of Newton iterations when solving for the universal anomaly
+const MAX_ITERATIONS: usize = 50;
+
+/// Relative tolerance of the universal anomaly
+const TOLERANCE: f64 = 1e-12;
+
+/// Stumpff function `C(z)`.
+#[inline]
+fn stumpff_c(z: f64) -> f64 {
+    if z > 1e-6 {
+        (1.0 - z.sqrt().cos()) / z
+    } else if z < -1e-6 {
+        ((-z).sqrt().cosh() - 1.0) / -z
+    } else {
+        1.0 / 2.0 - z / 24.0 + z * z / 720.0
+    }
+}
+
+/// Stumpff function `S(z)`.
+#[inline]
+fn stumpff_s(z: f64) -> f64 {
+    if z > 1e-6 {
+        let sqrt_z = z.sqrt();
+        (sqrt_z - sqrt_z.sin()) / (sqrt_z * sqrt_z * sqrt_z)
+    } else if z < -1e-6 {
+        let sqrt_z = (-z).sqrt();
+        (sqrt_z.sinh() - sqrt_z) / (sqrt_z * sqrt_z * sqrt_z)
+    } else {
+        1.0 / 6.0 - z / 120.0 + z * z / 5040.0
+    }
+}
+
+/// Propagates a relative state vector along its Kepler orbit by `dt`.
+///
+/// Uses the universal variable formulation, so circular, elliptic, parabolic and
+/// hyperbolic orbits are all handled the same way.
+///
+/// # Parameters
+/// - `mu`: Gravitational parameter `G * (M + m)` of the two bodies
+/// - `pos`, `vel`: Position and velocity relative to the parent
+/// - `dt`: Time to propagate, may be negative
+///
+/// # Returns
+///
+/// The relative position and velocity after `dt`. Without gravity (`mu <= 0`) or at
+/// the parent's center, the body moves in a straight line.
+pub fn propagate_kepler(mu: f32, pos: Vec3A, vel: Vec3A, dt: f32) -> (Vec3A, Vec3A) {
+    let (r0_vec, v0_vec) = (pos.as_dvec3(), vel.as_dvec3());
+    let (mu, dt) = (f64::from(mu), f64::from(dt));
+    let r0 = r0_vec.length();
+
+    if mu <= 0.0 || r0 == 0.0 {
+        return (pos + vel * dt as f32, vel);
+    }
+
+    let sqrt_mu = mu.sqrt();
+    let vr0 = r0_vec.dot(v0_vec) / r0;
+
+    // Reciprocal of the semi-major axis, negative for hyperbolic orbits
+    let alpha = 2.0 / r0 - v0_vec.length_squared() / mu;
+
+    // Solve Kepler's equation for the universal anomaly with Newton's method
+    let mut chi = sqrt_mu * dt / r0;
+    for _ in 0..MAX_ITERATIONS {
+        let chi2 = chi * chi;
+        let z = alpha * chi2;
+        let (c, s) = (stumpff_c(z), stumpff_s(z));
+
+        let f = r0 * vr0 / sqrt_mu * chi2 * c + (1.0 - alpha * r0) * chi2 * chi * s + r0 * chi
+            - sqrt_mu * dt;
+        let df = r0 * vr0 / sqrt_mu * chi * (1.0 - z * s) + (1.0 - alpha * r0) * chi2 * c + r0;
+
+        let step = f / df;
+        chi -= step;
+
+        if step.abs() <= TOLERANCE * chi.abs().max(1.0) {
+            break;
+        }
+    }
+
+    // Lagrange coefficients
+    let chi2 = chi * chi;
+    let z = alpha * chi2;
+    let (c, s) = (stumpff_c(z), stumpff_s(z));
+
+    let f = 1.0 - chi2 / r0 * c;
+    let g = dt - chi2 * chi / sqrt_mu * s;
+    let r_vec: DVec3 = f * r0_vec + g * v0_vec;
+    let r = r_vec.length();
+
+    let f_dot = sqrt_mu / (r * r0) * (z * chi * s - chi);
+    let g_dot = 1.0 - chi2 / r * c;
+    let v_vec = f_dot * r0_vec + g_dot * v0_vec;
+
+    (r_vec.as_vec3a(), v_vec.as_vec3a())
+}
+
+/// Velocity relative to the parent that puts a body at `pos` on the periapsis of an orbit
+/// with the given eccentricity, or on a circular orbit for an eccentricity of 0.
+///
+/// The orbital plane is taken from the current relative velocity `vel`. If that has no
+/// sideways component, the orbit is prograde around +Y, as close to the XZ plane as
+/// possible.
+pub fn periapsis_velocity(mu: f32, pos: Vec3A, vel: Vec3A, eccentricity: f32) -> Vec3A {
+    let r = pos.length();
+    if mu <= 0.0 || r == 0.0 {
+        return vel;
+    }
+
+    let radial = pos / r;
+    let normal = radial
+        .cross(vel)
+        .try_normalize()
+        .or_else(|| (Vec3A::Y - radial * radial.y).try_normalize())
+        .unwrap_or_else(|| radial.any_orthonormal_vector());
+
+    normal.cross(radial) * (mu * (1.0 + eccentricity) / r).sqrt()
+}
+
+/// Below this, orbits are treated as circular, parabolic or equatorial. Inputs are `f32`,
+/// so smaller deviations are indistinguishable from rounding errors
+const DEGENERATE_EPSILON: f64 = 1e-6;
+
+/// Classical Keplerian elements of an orbit around a parent body.
+///
+/// Angles are in radians. Elements that are undefined for degenerate orbits are zero:
+///
+/// - Circular orbits have no periapsis, so `argument_of_periapsis` is zero and
+///   `true_anomaly` is measured from the ascending node.
+/// - Equatorial orbits have no ascending node, so `longitude_of_ascending_node` is zero
+///   and the other angles are measured from the reference direction.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct OrbitalElements {
+    /// Semi-major axis, negative for hyperbolic orbits.
+    ///
+    /// For parabolic orbits (`eccentricity == 1`), where the semi-major axis is infinite,
+    /// this is the periapsis distance instead.
+    pub semi_major_axis: f32,
+
+    /// Eccentricity: 0 is circular, below 1 elliptic, 1 parabolic and above 1 hyperbolic
+    pub eccentricity: f32,
+
+    /// Tilt of the orbital plane against the reference plane, from 0 to π.
+    /// Orbits above π/2 are retrograde
+    pub inclination: f32,
+
+    /// Angle from the reference direction to the ascending node
+    pub longitude_of_ascending_node: f32,
+
+    /// Angle from the ascending node to the periapsis
+    pub argument_of_periapsis: f32,
+
+    /// Angle from the periapsis to the body
+    pub true_anomaly: f32,
+}
+
+/// Converts from Godot's Y-up frame to the Z-up frame of the textbook formulas.
+#[inline]
+fn to_z_up(v: Vec3A) -> DVec3 {
+    DVec3::new(f64::from(v.x), -f64::from(v.z), f64::from(v.y))
+}
+
+/// Converts from the Z-up frame of the textbook formulas to Godot's Y-up frame.
+#[inline]
+fn from_z_up(v: DVec3) -> Vec3A {
+    Vec3A::new(v.x as f32, v.z as f32, -v.y as f32)
+}
+
+/// Angle from `a` to `b` around `axis`, in `[0, 2π)`.
+#[inline]
+fn angle_around(axis: DVec3, a: DVec3, b: DVec3) -> f64 {
+    axis.dot(a.cross(b)).atan2(a.dot(b)).rem_euclid(TAU)
+}
+
+impl OrbitalElements {
+    /// Computes the elements of the orbit with the given position and velocity
+    /// relative to the parent.
+    ///
+    /// Returns `None` for degenerate states without an orbital plane, like radial
+    /// trajectories or bodies at the parent's center.
+    pub fn from_state(mu: f32, pos: Vec3A, vel: Vec3A) -> Option<Self> {
+        let (r_vec, v_vec, mu) = (to_z_up(pos), to_z_up(vel), f64::from(mu));
+        let r = r_vec.length();
+        let h_vec = r_vec.cross(v_vec);
+        let h = h_vec.length();
+
+        if mu <= 0.0 || r == 0.0 || h <= DEGENERATE_EPSILON * r * v_vec.length() {
+            return None;
+        }
+
+        let h_dir = h_vec / h;
+        let e_vec = ((v_vec.length_squared() - mu / r) * r_vec - r_vec.dot(v_vec) * v_vec) / mu;
+        let e = e_vec.length();
+
+        let semi_major_axis = if (e - 1.0).abs() < DEGENERATE_EPSILON {
+            h * h / (2.0 * mu)
+        } else {
+            (h * h / mu) / (1.0 - e * e)
+        };
+
+        let inclination = h_dir.z.clamp(-1.0, 1.0).acos();
+
+        // Line of nodes, pointing to the ascending node
+        let node = DVec3::Z.cross(h_dir);
+        let equatorial = node.length() < DEGENERATE_EPSILON;
+        let (longitude_of_ascending_node, reference) = if equatorial {
+            (0.0, DVec3::X)
+        } else {
+            (node.y.atan2(node.x).rem_euclid(TAU), node.normalize())
+        };
+
+        let circular = e < DEGENERATE_EPSILON;
+        let (argument_of_periapsis, periapsis) = if circular {
+            (0.0, reference)
+        } else {
+            (angle_around(h_dir, reference, e_vec), e_vec / e)
+        };
+
+        Some(Self {
+            semi_major_axis: semi_major_axis as f32,
+            eccentricity: e as f32,
+            inclination: inclination as f32,
+            longitude_of_ascending_node: longitude_of_ascending_node as f32,
+            argument_of_periapsis: argument_of_periapsis as f32,
+            true_anomaly: angle_around(h_dir, periapsis, r_vec) as f32,
+        })
+    }
+
+    /// Computes the position and velocity relative to the parent.
+    ///
+    /// The sign of the semi-major axis is ignored, the eccentricity decides the type of
+    /// orbit. Returns `None` if the orbit has no size, or if a hyperbolic orbit never
+    /// reaches the true anomaly.
+    pub fn to_state(&self, mu: f32) -> Option<(Vec3A, Vec3A)> {
+        let mu = f64::from(mu);
+        let e = f64::from(self.eccentricity.max(0.0));
+        let a = f64::from(self.semi_major_axis).abs();
+        let nu = f64::from(self.true_anomaly);
+
+        // Semi-latus rectum
+        let p = if (e - 1.0).abs() < DEGENERATE_EPSILON {
+            2.0 * a
+        } else {
+            a * (1.0 - e * e).abs()
+        };
+
+        let denominator = 1.0 + e * nu.cos();
+        if mu <= 0.0 || p <= 0.0 || denominator <= DEGENERATE_EPSILON {
+            return None;
+        }
+
+        // State in the perifocal frame, with the periapsis along +X
+        let r = p / denominator;
+        let r_pf = DVec3::new(r * nu.cos(), r * nu.sin(), 0.0);
+        let v_pf = (mu / p).sqrt() * DVec3::new(-nu.sin(), e + nu.cos(), 0.0);
+
+        let rotation = DQuat::from_rotation_z(f64::from(self.longitude_of_ascending_node))
+            * DQuat::from_rotation_x(f64::from(self.inclination))
+            * DQuat::from_rotation_z(f64::from(self.argument_of_periapsis));
+
+        Some((from_z_up(rotation * r_pf), from_z_up(rotation * v_pf)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+    #[test]
+    fn circular_orbit_returns_after_one_period() {
+        let (mu, radius) = (100.0, 10.0);
+        let pos = Vec3A::new(radius, 0.0, 0.0);
+        let vel = Vec3A::new(0.0, 0.0, (mu / radius).sqrt());
+        let period = TAU * (radius.powi(3) / mu).sqrt();
+
+        let (half_pos, half_vel) = propagate_kepler(mu, pos, vel, period / 2.0);
+        assert!(half_pos.abs_diff_eq(-pos, 1e-3), "{half_pos}");
+        assert!(half_vel.abs_diff_eq(-vel, 1e-3), "{half_vel}");
+
+        let (end_pos, end_vel) = propagate_kepler(mu, pos, vel, period);
+        assert!(end_pos.abs_diff_eq(pos, 1e-3), "{end_pos}");
+        assert!(end_vel.abs_diff_eq(vel, 1e-3), "{end_vel}");
+    }
+
+    #[test]
+    fn conserves_energy_and_angular_momentum() {
+        let mu = 50.0;
+        let energy = |p: Vec3A, v: Vec3A| v.length_squared() / 2.0 - mu / p.length();
+
+        // Elliptic, parabolic and hyperbolic orbits
+        for speed in [2.0, 10.0f32.sqrt(), 5.0] {
+            let pos = Vec3A::new(10.0, 2.0, 0.0);
+            let vel = Vec3A::new(0.5, speed, 0.3);
+
+            for dt in [-3.0, 0.1, 7.5] {
+                let (p, v) = propagate_kepler(mu, pos, vel, dt);
+
+                assert!((energy(p, v) - energy(pos, vel)).abs() < 1e-3);
+                assert!(p.cross(v).abs_diff_eq(pos.cross(vel), 1e-2));
+            }
+        }
+    }
+
+    fn assert_round_trip(mu: f32, elements: OrbitalElements) {
+        let (pos, vel) = elements.to_state(mu).expect("Elements should be valid");
+        let result = OrbitalElements::from_state(mu, pos, vel).expect("State should be valid");
+
+        let (pos2, vel2) = result.to_state(mu).unwrap();
+        assert!(pos.abs_diff_eq(pos2, 1e-3), "{elements:?} -> {result:?}");
+        assert!(vel.abs_diff_eq(vel2, 1e-3), "{elements:?} -> {result:?}");
+
+        assert!((result.eccentricity - elements.eccentricity).abs() < 1e-4);
+        assert!((result.semi_major_axis.abs() - elements.semi_major_axis.abs()).abs() < 1e-2);
+        assert!((result.inclination - elements.inclination).abs() < 1e-4);
+    }
+
+    #[test]
+    fn elements_round_trip() {
+        let general = OrbitalElements {
+            semi_major_axis: 20.0,
+            eccentricity: 0.3,
+            inclination: 0.4,
+            longitude_of_ascending_node: 1.0,
+            argument_of_periapsis: 2.0,
+            true_anomaly: 3.0,
+        };
+
+        let cases = [
+            general,
+            // Circular
+            OrbitalElements {
+                eccentricity: 0.0,
+                argument_of_periapsis: 0.0,
+                ..general
+            },
+            // Equatorial, prograde and retrograde
+            OrbitalElements {
+                inclination: 0.0,
+                longitude_of_ascending_node: 0.0,
+                ..general
+            },
+            OrbitalElements {
+                inclination: PI,
+                longitude_of_ascending_node: 0.0,
+                ..general
+            },
+            // Circular and equatorial
+            OrbitalElements {
+                eccentricity: 0.0,
+                inclination: 0.0,
+                longitude_of_ascending_node: 0.0,
+                argument_of_periapsis: 0.0,
+                ..general
+            },
+            // Polar
+            OrbitalElements {
+                inclination: FRAC_PI_2,
+                ..general
+            },
+            // Hyperbolic
+            OrbitalElements {
+                semi_major_axis: -20.0,
+                eccentricity: 1.5,
+                true_anomaly: 1.0,
+                ..general
+            },
+            // Parabolic
+            OrbitalElements {
+                semi_major_axis: 5.0,
+                eccentricity: 1.0,
+                true_anomaly: 1.0,
+                ..general
+            },
+        ];
+
+        for elements in cases {
+            assert_round_trip(100.0, elements);
+        }
+    }
+
+    #[test]
+    fn prograde_equatorial_orbit_is_counter_clockwise_from_above() {
+        let elements = OrbitalElements {
+            semi_major_axis: 10.0,
+            ..Default::default()
+        };
+
+        let (pos, vel) = elements.to_state(100.0).unwrap();
+
+        assert!(pos.abs_diff_eq(Vec3A::X * 10.0, 1e-5));
+        assert!(pos.cross(vel).y > 0.0);
+        assert!((vel.length() - 10.0f32.sqrt()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn periapsis_velocity_matches_elements() {
+        let mu = 100.0;
+        let pos = Vec3A::new(3.0, 1.0, -4.0);
+
+        for eccentricity in [0.0, 0.5] {
+            let vel = periapsis_velocity(mu, pos, Vec3A::ZERO, eccentricity);
+            let elements = OrbitalElements::from_state(mu, pos, vel).unwrap();
+
+            assert!((elements.eccentricity - eccentricity).abs() < 1e-4);
+            assert!(pos.cross(vel).y > 0.0);
+            if eccentricity > 0.0 {
+                let nu = elements.true_anomaly;
+                assert!(nu.min(TAU - nu) < 1e-3, "{nu}");
+            }
+        }
+    }
+
+    #[test]
+    fn hyperbolic_true_anomaly_beyond_asymptote_is_rejected() {
+        let elements = OrbitalElements {
+            semi_major_axis: -10.0,
+            eccentricity: 2.0,
+            true_anomaly: 2.5,
+            ..Default::default()
+        };
+
+        assert!(elements.to_state(1.0).is_none());
+    }
+}
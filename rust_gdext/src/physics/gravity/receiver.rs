@@ -0,0 +1,148 @@
+//! Gravity for nodes outside the simulation.
+
+use super::controller::GravityController;
+use crate::{from_glam_vec3, to_glam_vec3};
+use glam::Vec3A;
+use godot::{
+    classes::{CharacterBody3D, RigidBody3D},
+    prelude::*,
+};
+
+/// Applies the gravity of a [`GravityController`] to its parent every physics tick.
+///
+/// Add it as a child of a `RigidBody3D` or `CharacterBody3D`, so ships and players orbit
+/// the simulated bodies without being simulated themselves.
+///
+/// - Rigid bodies receive the gravity as a central force. Set their `gravity_scale` to 0,
+///   so they don't also fall along the project's default gravity.
+/// - Character bodies have the acceleration added to their `velocity`, which their script
+///   then moves by with `move_and_slide`.
+///
+/// The acceleration is also stored in `acceleration` for scripts to read, for example to
+/// orient a player towards the ground.
+#[derive(GodotClass)]
+#[class(init, base = Node)]
+pub struct GravityReceiver {
+    base: Base<Node>,
+
+    /// Controller whose bodies attract the parent
+    #[export]
+    pub controller: Option<Gd<GravityController>>,
+
+    /// Multiplier of the applied gravity
+    #[export]
+    #[init(val = 1.0)]
+    pub gravity_scale: f32,
+
+    /// Whether to point the `up_direction` of a character body away from the gravity
+    #[export]
+    pub align_up_direction: bool,
+
+    /// Gravitational acceleration at the parent during the last physics tick
+    #[var]
+    pub acceleration: Vector3,
+}
+
+#[godot_api]
+impl INode for GravityReceiver {
+    fn physics_process(&mut self, delta: f64) {
+        let (Some(controller), Some(parent)) = (&self.controller, self.base().get_parent()) else {
+            return;
+        };
+        let Ok(parent) = parent.try_cast::<Node3D>() else {
+            return;
+        };
+
+        let acceleration = global_acceleration(
+            controller.get_global_transform(),
+            parent.get_global_position(),
+            |position| {
+                controller
+                    .bind()
+                    .sample_accelerations_at(&[position])
+                    .first()
+                    .copied()
+                    .unwrap_or_default()
+            },
+        );
+        self.acceleration = acceleration * self.gravity_scale;
+
+        match parent.try_cast::<RigidBody3D>() {
+            Ok(mut rigid_body) => {
+                let force = self.acceleration * rigid_body.get_mass();
+                rigid_body.apply_central_force(force);
+            }
+            Err(parent) => {
+                if let Ok(mut character) = parent.try_cast::<CharacterBody3D>() {
+                    let velocity = character.get_velocity() + self.acceleration * delta as f32;
+                    character.set_velocity(velocity);
+
+                    if self.align_up_direction && self.acceleration != Vector3::ZERO {
+                        character.set_up_direction(-self.acceleration.normalized());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Samples the gravity of a controller at a global position.
+///
+/// The bodies are simulated in the controller's local space, so `global_pos` is moved into
+/// it and the acceleration returned by `sample` is rotated and scaled back into global space.
+fn global_acceleration(
+    controller_transform: Transform3D,
+    global_pos: Vector3,
+    sample: impl FnOnce(Vec3A) -> Vec3A,
+) -> Vector3 {
+    let local_pos = controller_transform.affine_inverse() * global_pos;
+    controller_transform.basis * from_glam_vec3(sample(to_glam_vec3(local_pos)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::gravity::{
+        NBodyGravityCalculator, controller::SimulatedBody, direct_summation::DirectSummation,
+        field::accelerations_at,
+    };
+
+    #[test]
+    fn sampled_gravity_matches_the_simulation() {
+        // A massless probe feels the same gravity as a point sampled at its position
+        let bodies = [
+            SimulatedBody::test(1, 1000.0, Vec3A::ZERO, Vec3A::ZERO),
+            SimulatedBody::test(2, 10.0, Vec3A::new(50.0, 0.0, 0.0), Vec3A::ZERO),
+            SimulatedBody::test(3, 0.0, Vec3A::new(20.0, 5.0, -3.0), Vec3A::ZERO),
+        ];
+
+        let simulated = DirectSummation::new(&bodies).calc_accs::<false>(2.0)[2];
+        let sampled = accelerations_at(2.0, &bodies[..2], &[bodies[2].pos])[0];
+        assert!(sampled.distance(simulated) < 1e-6 * simulated.length());
+    }
+
+    #[test]
+    fn gravity_follows_the_controller_transform() {
+        // Rotated a quarter turn around Y, scaled by 2 and moved
+        let basis = Basis::from_axis_angle(Vector3::UP, std::f32::consts::FRAC_PI_2)
+            .scaled(Vector3::splat(2.0));
+        let transform = Transform3D::new(basis, Vector3::new(10.0, 0.0, 0.0));
+
+        // Point masses at the local origin pull towards it
+        let pull = |pos: Vec3A| -pos;
+
+        // 4 along global X is 2 along local Z after undoing the rotation and scale
+        let global_pos = Vector3::new(14.0, 0.0, 0.0);
+        let mut local_pos = Vec3A::ZERO;
+        let acceleration = global_acceleration(transform, global_pos, |pos| {
+            local_pos = pos;
+            pull(pos)
+        });
+
+        assert!(local_pos.distance(Vec3A::Z * 2.0) < 1e-5, "{local_pos}");
+        assert!(
+            acceleration.distance_to(Vector3::new(-4.0, 0.0, 0.0)) < 1e-5,
+            "{acceleration}"
+        );
+    }
+}
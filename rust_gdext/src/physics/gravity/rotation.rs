@@ -0,0 +1,125 @@
+//! Rotational state of bodies.
+//!
+//! Bodies are treated as uniform spheres with a moment of inertia of `2/5 m r²`. They
+//! rotate freely with a constant angular velocity, except when merges add the orbital
+//! angular momentum of the absorbed bodies, or when tides slowly lock them to their primary.
+
+use super::{HasMass, HasPosition, HasVelocity, controller::SimulatedBody};
+use glam::{Quat, Vec3A};
+
+/// Moment of inertia of a uniform sphere.
+#[inline]
+pub fn moment_of_inertia(mass: f32, radius: f32) -> f32 {
+    0.4 * mass * radius * radius
+}
+
+/// Rotates `orientation` by the angular velocity over `delta` seconds.
+#[inline]
+pub fn integrate_orientation(orientation: Quat, angular_velocity: Vec3A, delta: f32) -> Quat {
+    if angular_velocity == Vec3A::ZERO {
+        return orientation;
+    }
+
+    (Quat::from_scaled_axis((angular_velocity * delta).into()) * orientation).normalize()
+}
+
+/// Gradually locks the rotation of bodies to their orbit around their primary.
+///
+/// The primary of a body is the heavier body with the strongest tidal field at its
+/// position, `M / d³`. The angular velocity of the body relaxes towards the orbital angular
+/// velocity around the primary at a rate of `strength * (M / m) * (r / d)³` per second,
+/// so small moons close to massive planets lock first. At the same rate, the body turns
+/// its forward axis (`-Z`) towards the primary, so locked moons always show it one face.
+///
+/// This is a gameplay approximation: the angular momentum lost by the body is not
+/// transferred to the orbit. Only bodies with a physical radius are affected.
+pub fn apply_tidal_locking(strength: f32, delta: f32, bodies: &mut [SimulatedBody]) {
+    if strength <= 0.0 {
+        return;
+    }
+
+    for i in 0..bodies.len() {
+        let body = &bodies[i];
+        let (Some(radius), mass) = (body.radius, body.get_mass()) else {
+            continue;
+        };
+        if mass <= 0.0 {
+            continue;
+        }
+
+        let primary = bodies
+            .iter()
+            .filter(|p| p.get_mass() > mass)
+            .map(|p| {
+                (
+                    p,
+                    p.get_mass() / p.get_pos().distance_squared(body.get_pos()).powf(1.5),
+                )
+            })
+            .filter(|(_, tide)| tide.is_finite())
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(p, _)| p);
+
+        let Some(primary) = primary else {
+            continue;
+        };
+
+        let rel_pos = body.get_pos() - primary.get_pos();
+        let rel_vel = body.get_vel() - primary.get_vel();
+        let dist_sq = rel_pos.length_squared();
+
+        let orbital_angular_velocity = rel_pos.cross(rel_vel) / dist_sq;
+        let rate = strength * (primary.get_mass() / mass) * (radius * radius / dist_sq).powf(1.5);
+        let blend = 1.0 - (-rate * delta).exp();
+
+        let body = &mut bodies[i];
+        body.angular_velocity = body.angular_velocity.lerp(orbital_angular_velocity, blend);
+
+        let facing = body.orientation * Vec3A::NEG_Z;
+        if let Some(to_primary) = (-rel_pos).try_normalize() {
+            let locked =
+                Quat::from_rotation_arc(facing.into(), to_primary.into()) * body.orientation;
+            body.orientation = body.orientation.slerp(locked, blend).normalize();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moon_locks_to_planet() {
+        let planet = SimulatedBody {
+            radius: Some(5.0),
+            ..SimulatedBody::test(1, 1000.0, Vec3A::ZERO, Vec3A::ZERO)
+        };
+        let moon = SimulatedBody {
+            angular_velocity: Vec3A::Y * 3.0,
+            radius: Some(2.0),
+            ..SimulatedBody::test(2, 1.0, Vec3A::X * 20.0, Vec3A::NEG_Z * 50.0f32.sqrt())
+        };
+        let mut bodies = [planet, moon];
+
+        for _ in 0..100 {
+            apply_tidal_locking(1.0, 0.1, &mut bodies);
+        }
+
+        // One rotation per orbit, around the orbit normal
+        let orbital = bodies[1].pos.cross(bodies[1].vel) / bodies[1].pos.length_squared();
+        assert!(bodies[1].angular_velocity.abs_diff_eq(orbital, 1e-3));
+        assert_eq!(bodies[0].angular_velocity, Vec3A::ZERO);
+
+        // Facing the planet
+        let facing = bodies[1].orientation * Vec3A::NEG_Z;
+        assert!(facing.abs_diff_eq(Vec3A::NEG_X, 1e-3), "{facing}");
+        assert_eq!(bodies[0].orientation, Quat::IDENTITY);
+    }
+
+    #[test]
+    fn orientation_follows_angular_velocity() {
+        let q = integrate_orientation(Quat::IDENTITY, Vec3A::Y * std::f32::consts::PI, 0.5);
+
+        assert!((q * glam::Vec3::X).abs_diff_eq(glam::Vec3::NEG_Z, 1e-5));
+    }
+}
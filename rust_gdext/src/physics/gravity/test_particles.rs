@@ -0,0 +1,127 @@
+//! Massless test particles.
+//!
+//! Test particles are accelerated by the massive bodies but don't attract anything
+//! themselves, so they are never part of the source set of the gravity calculation.
+//! Stepping `M` particles through the field of `N` bodies costs `O(M * N)` with direct
+//! summation, or `O(M * log N)` with the octree, instead of `O((M + N)²)`.
+//!
+//! This makes them a good fit for asteroid belts, planetary rings, debris and spacecraft
+//! whose mass is negligible compared to the bodies around them.
+
+use super::{HasPosition, HasVelocity, controller::SimulatedBody, field::accelerations_at};
+use glam::Vec3A;
+
+/// A massless particle moving through the gravity field of the simulated bodies.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TestParticle {
+    pub pos: Vec3A,
+    pub vel: Vec3A,
+}
+
+impl HasPosition for TestParticle {
+    #[inline(always)]
+    fn get_pos(&self) -> Vec3A {
+        self.pos
+    }
+
+    #[inline(always)]
+    fn set_pos(&mut self, pos: Vec3A) {
+        self.pos = pos;
+    }
+}
+
+impl HasVelocity for TestParticle {
+    #[inline(always)]
+    fn get_vel(&self) -> Vec3A {
+        self.vel
+    }
+
+    #[inline(always)]
+    fn set_vel(&mut self, vel: Vec3A) {
+        self.vel = vel;
+    }
+}
+
+/// Advances test particles by one time step through the gravity field of `sources`.
+///
+/// Uses the same integrator as [`GravityController::step_time`], so it should be called
+/// with the sources' state from the start of the step, before they are stepped themselves.
+///
+/// [`GravityController::step_time`]: super::controller::GravityController::step_time
+pub fn step_test_particles(
+    grav_const: f32,
+    delta: f32,
+    sources: &[SimulatedBody],
+    particles: &mut [TestParticle],
+) {
+    if particles.is_empty() {
+        return;
+    }
+
+    let points = particles
+        .iter()
+        .map(HasPosition::get_pos)
+        .collect::<Vec<_>>();
+    let accelerations = accelerations_at(grav_const, sources, &points);
+
+    particles
+        .iter_mut()
+        .zip(accelerations)
+        .for_each(|(particle, acc)| {
+            particle.vel += acc * delta;
+            particle.pos += particle.vel * delta;
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        octree::morton_based::MortonBasedOctree,
+        physics::gravity::{NBodyGravityCalculator, direct_summation::DirectSummation},
+    };
+
+    #[test]
+    fn direct_and_octree_agree() {
+        let sources = (0..500)
+            .map(|i| {
+                let t = i as f32;
+                SimulatedBody::test(
+                    i + 1,
+                    1.0 + (t * 0.37).fract(),
+                    Vec3A::new((t * 1.3).sin(), (t * 0.7).cos(), (t * 0.11).sin()) * 100.0,
+                    Vec3A::ZERO,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let points = [
+            Vec3A::ZERO,
+            Vec3A::splat(250.0),
+            Vec3A::new(-40.0, 10.0, 5.0),
+        ];
+
+        let direct = DirectSummation::new(&sources).calc_accs_at::<false>(1.0, &points);
+        let octree = MortonBasedOctree::new(&sources).calc_accs_at::<false>(1.0, &points);
+
+        for (d, o) in direct.iter().zip(&octree) {
+            assert!((*d - *o).length() <= d.length() * 0.05, "{d} vs {o}");
+        }
+    }
+
+    #[test]
+    fn particle_follows_circular_orbit() {
+        let sources = [SimulatedBody::test(1, 1000.0, Vec3A::ZERO, Vec3A::ZERO)];
+        let radius = 10.0;
+        let mut particles = [TestParticle {
+            pos: Vec3A::X * radius,
+            vel: Vec3A::Y * (1000.0f32 / radius).sqrt(),
+        }];
+
+        for _ in 0..1000 {
+            step_test_particles(1.0, 0.01, &sources, &mut particles);
+        }
+
+        assert!((particles[0].pos.length() - radius).abs() < 0.1);
+    }
+}
@@ -0,0 +1,199 @@
+//! Tidal effects between bodies.
+//!
+//! A satellite that comes closer to its primary than the Roche limit is torn apart by
+//! the primary's tidal forces. Here the satellite is replaced by a cloud of massless debris
+//! that the primary's tides shear into a ring along the satellite's orbit.
+
+use super::{
+    HasMass, HasPosition, HasVelocity, controller::SimulatedBody, test_particles::TestParticle,
+};
+use glam::Vec3A;
+use std::f32::consts::PI;
+
+/// Angle between consecutive points of a Fibonacci sphere, `π * (3 - √5)`
+const GOLDEN_ANGLE: f32 = PI * 0.763_932;
+
+/// A satellite found inside the Roche limit of its primary.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Disruption {
+    /// Index of the body being torn apart
+    pub satellite: usize,
+    /// Index of the body whose tides tear it apart
+    pub primary: usize,
+}
+
+/// Roche limit of a rigid satellite around a primary.
+///
+/// Uses `d = R_s * cbrt(2 * M_p / m_s)`, which is equivalent to the density form
+/// `d = R_p * cbrt(2 * ρ_p / ρ_s)` but only needs the satellite's radius.
+#[inline]
+pub fn roche_limit(primary_mass: f32, satellite_mass: f32, satellite_radius: f32) -> f32 {
+    satellite_radius * (2.0 * primary_mass / satellite_mass).cbrt()
+}
+
+/// Finds every body that is inside the Roche limit of a more massive body.
+///
+/// Only dynamic bodies with a physical radius can be disrupted. If a satellite is inside the
+/// Roche limit of several bodies, the one it penetrates deepest is chosen as its primary.
+pub fn detect_disruptions(bodies: &[SimulatedBody]) -> Vec<Disruption> {
+    bodies
+        .iter()
+        .enumerate()
+        .filter_map(|(satellite, sat)| {
+            let radius = sat.radius?;
+            if sat.get_mass() <= 0.0 || !sat.motion.is_dynamic() {
+                return None;
+            }
+
+            bodies
+                .iter()
+                .enumerate()
+                .filter(|(_, p)| p.get_mass() > sat.get_mass())
+                .map(|(primary, p)| {
+                    let limit = roche_limit(p.get_mass(), sat.get_mass(), radius);
+                    (primary, p.get_pos().distance(sat.get_pos()) / limit)
+                })
+                .filter(|&(_, depth)| depth < 1.0)
+                .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(primary, _)| Disruption { satellite, primary })
+        })
+        .collect()
+}
+
+/// The debris of a disrupted satellite.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Debris {
+    /// The fragments, as massless particles
+    pub particles: Vec<TestParticle>,
+    /// Mass of each fragment, the satellite's mass split evenly between them
+    ///
+    /// The particles don't attract anything, this is only used to account for where the
+    /// satellite's mass went.
+    pub fragment_mass: f32,
+}
+
+/// Breaks a satellite into a cloud of debris.
+///
+/// The fragments fill the satellite's volume and move like the satellite did, including
+/// its rotation, so together they carry its mass and momentum. Fragments at different
+/// distances from the primary orbit at different speeds, so the primary's tides then
+/// shear the cloud into a ring along the satellite's orbit.
+///
+/// Returns no fragments if `n_debris` is zero.
+pub fn disrupt(satellite: &SimulatedBody, n_debris: usize) -> Debris {
+    if n_debris == 0 {
+        return Debris::default();
+    }
+
+    let radius = satellite.radius.unwrap_or_default();
+    let n = n_debris as f32;
+
+    // Fibonacci sphere directions with golden ratio distances give an even, deterministic
+    // spread through the volume
+    let offsets = (0..n_debris)
+        .map(|i| {
+            let z = 1.0 - 2.0 * (i as f32 + 0.5) / n;
+            let (sin, cos) = (i as f32 * GOLDEN_ANGLE).sin_cos();
+            let dir = Vec3A::new(cos, sin, 0.0) * (1.0 - z * z).max(0.0).sqrt() + Vec3A::Z * z;
+            dir * radius * (i as f32 * 0.618_034).fract().cbrt()
+        })
+        .collect::<Vec<_>>();
+
+    // Center the cloud on the satellite, so the rotation adds no net momentum
+    let center = offsets.iter().copied().sum::<Vec3A>() / n;
+
+    let particles = offsets
+        .into_iter()
+        .map(|offset| {
+            let offset = offset - center;
+            TestParticle {
+                pos: satellite.get_pos() + offset,
+                vel: satellite.get_vel() + satellite.angular_velocity.cross(offset),
+            }
+        })
+        .collect();
+
+    Debris {
+        particles,
+        fragment_mass: satellite.get_mass() / n,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roche_limit_of_the_moon() {
+        assert!((roche_limit(4.0, 1.0, 1.0) - 2.0).abs() < 1e-6);
+
+        // The rigid Roche limit of the Moon around the Earth is about 9,500 km
+        let limit = roche_limit(5.972e24, 7.342e22, 1.7374e6);
+        assert!((limit / 9.49e6 - 1.0).abs() < 0.01, "Got {limit} m");
+    }
+
+    #[test]
+    fn only_moons_inside_the_limit_are_disrupted() {
+        let moon = |id, x| SimulatedBody {
+            radius: Some(1.0),
+            ..SimulatedBody::test(id, 1.0, Vec3A::X * x, Vec3A::ZERO)
+        };
+
+        // Roche limit of the moons is cbrt(2000) ≈ 12.6
+        let bodies = [
+            SimulatedBody::test(1, 1000.0, Vec3A::ZERO, Vec3A::ZERO),
+            moon(2, 20.0),
+            moon(3, -10.0),
+            // Without a radius or density, bodies can't be disrupted
+            SimulatedBody::test(4, 1.0, Vec3A::Z * 5.0, Vec3A::ZERO),
+        ];
+
+        assert_eq!(
+            detect_disruptions(&bodies),
+            [Disruption {
+                satellite: 2,
+                primary: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn debris_conserves_mass_and_momentum() {
+        let satellite = SimulatedBody {
+            radius: Some(2.0),
+            angular_velocity: Vec3A::new(0.3, 1.0, 0.0),
+            ..SimulatedBody::test(
+                1,
+                3.0,
+                Vec3A::new(10.0, 0.0, 5.0),
+                Vec3A::new(0.0, 2.0, -4.0),
+            )
+        };
+
+        let debris = disrupt(&satellite, 50);
+        assert_eq!(debris.particles.len(), 50);
+
+        let mass = debris.fragment_mass * debris.particles.len() as f32;
+        assert!((mass - satellite.mass).abs() < 1e-5);
+
+        let momentum: Vec3A = debris
+            .particles
+            .iter()
+            .map(|p| debris.fragment_mass * p.vel)
+            .sum();
+        let expected = satellite.mass * satellite.vel;
+        assert!(
+            momentum.distance(expected) < 1e-4,
+            "Momentum {momentum} != {expected}"
+        );
+
+        // The fragments fill the satellite
+        assert!(
+            debris
+                .particles
+                .iter()
+                .all(|p| p.pos.distance(satellite.pos) < 2.0 * 2.0)
+        );
+        assert!(disrupt(&satellite, 0).particles.is_empty());
+    }
+}
@@ -39,7 +39,11 @@
 use std::{
     any::Any,
     panic::{AssertUnwindSafe, UnwindSafe},
-    sync::mpsc::{self, Receiver, RecvError, SendError, Sender, TryRecvError, channel},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvError, SendError, Sender, TryRecvError, channel},
+    },
     thread::JoinHandle,
 };
 
@@ -66,6 +70,33 @@ pub struct Worker<Res, Command> {
     result_receiver: Receiver<Res>,
 }
 
+/// A flag shared between threads to abort a running task.
+///
+/// The main thread keeps a clone and calls [`cancel`](Self::cancel) once the task is no
+/// longer needed. The worker checks [`is_cancelled`](Self::is_cancelled) regularly while
+/// running the task and stops early. Commands still queued in the channel are dropped by
+/// [`CommandBatch::latest`], but a task that already started can only be stopped this way.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests the task to stop.
+    #[inline(always)]
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the task was requested to stop.
+    #[inline(always)]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
 /// Provides methods for receiving commands in a worker thread.
 ///
 /// This struct wraps a command channel receiver and provides methods
@@ -249,6 +280,18 @@ where
         self.result_receiver.try_iter().last()
     }
 
+    /// Receives all available results from the worker without blocking, oldest first.
+    #[inline(always)]
+    pub fn try_recv_all(&self) -> mpsc::TryIter<'_, Res> {
+        self.result_receiver.try_iter()
+    }
+
+    /// Whether the worker thread has stopped, after a shutdown or because it panicked.
+    #[inline(always)]
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
     /// Joins the worker thread, waiting for it to finish.
     ///
     /// This method should be called when the worker is no longer needed